@@ -1026,8 +1026,20 @@ void doAction( const Game *game, const Action *action, State *state )
   }
 }
 
-static int rankHand( const Game *game, const State *state,
-		     const uint8_t player )
+int rankCards( const uint8_t *cards, const uint8_t numCards )
+{
+  int i;
+  Cardset c = emptyCardset();
+
+  for( i = 0; i < numCards; ++i ) {
+
+    addCardToCardset( &c, suitOfCard( cards[ i ] ), rankOfCard( cards[ i ] ) );
+  }
+
+  return rankCardset( c );
+}
+
+int rankHand( const Game *game, const State *state, const uint8_t player )
 {
   int i;
   Cardset c = emptyCardset();
@@ -187,6 +187,14 @@ uint8_t bcStart( const Game *game, const uint8_t round );
 /* get the total number of board cards dealt out after (zero based) round */
 uint8_t sumBoardCards( const Game *game, const uint8_t round );
 
+/* rank the hand of player made from their hole cards and the board cards
+   dealt out so far, where a larger rank is a better hand */
+int rankHand( const Game *game, const State *state, const uint8_t player );
+
+/* rank the best hand made from numCards cards, where a larger rank is a
+   better hand */
+int rankCards( const uint8_t *cards, const uint8_t numCards );
+
 /* return the value of a finished hand for a player
    returns a double because pots may be split when players tie
    WILL HAVE UNDEFINED BEHAVIOUR IF HAND ISN'T FINISHED
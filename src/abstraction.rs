@@ -0,0 +1,42 @@
+use crate::{Card, Game};
+
+
+/// Card abstraction which maps the cards seen by a player to a bucket, for
+/// `State::info_set_with`.
+pub trait CardAbstraction {
+    /// Bucket of the hole cards `hole` of a player with the board cards
+    /// `board` visible in the current round.
+    fn bucket(&self, game: &Game, hole: &[Card], board: &[Card]) -> u32;
+}
+
+/// Abstraction which keeps every set of hole cards in its own bucket and
+/// ignores the board, so it is lossless in games without board cards.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IdentityAbstraction;
+
+impl CardAbstraction for IdentityAbstraction {
+    fn bucket(&self, _game: &Game, hole: &[Card], _board: &[Card]) -> u32 {
+	let mut hole = hole.to_vec();
+	hole.sort_unstable();
+	hole.iter().fold(0, |bucket, &c| bucket << 8 | c as u32)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_cards;
+    use std::fs::File;
+
+    #[test]
+    fn identity() {
+	let game = Game::read(File::open("resources/holdem.nolimit.3p.game").unwrap());
+	let bucket = |hole: &str, board: &str| {
+	    IdentityAbstraction.bucket(&game, &parse_cards(hole).unwrap(), &parse_cards(board).unwrap())
+	};
+	assert_eq!(bucket("AsKh", ""), bucket("KhAs", "7h8d9c"));
+	assert_ne!(bucket("AsKh", ""), bucket("AsKd", ""));
+	assert_ne!(bucket("2c3c", ""), bucket("2c4c", ""));
+    }
+}
@@ -0,0 +1,223 @@
+//! Compact binary layout of states, for checkpoints which have to be small
+//! and fast to write.
+//!
+//! The game is not stored, it is given back to `State::from_bytes`. A state
+//! is laid out as:
+//!
+//! | bytes | content |
+//! |-------|---------|
+//! | 1 | version, currently 1 |
+//! | 4 | hand id, little endian |
+//! | 1 | number of rounds played so far, the current one included |
+//! | | for each round: the number of actions, then the actions |
+//! | | hole cards, `numHoleCards` per player, 255 when not dealt |
+//! | 1 | number of board cards, then the board cards |
+//!
+//! An action is one byte, 0 for fold, 1 for call and 2 for raise, and a
+//! raise is followed by its size as 4 little endian bytes.
+//!
+//! `from_bytes` replays the actions to rebuild the state.
+
+use acpc_server_sys as acpc;
+
+use crate::{Action, Game, ParseError, State, NOT_DEALT};
+
+
+/// Version of the binary layout.
+const VERSION: u8 = 1;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ParseError> {
+	if self.bytes.len() < n {
+	    return Err(invalid("unexpected end of the bytes"));
+	}
+	let (head, tail) = self.bytes.split_at(n);
+	self.bytes = tail;
+	Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, ParseError> {
+	Ok(self.take(1)?[0])
+    }
+
+    fn i32(&mut self) -> Result<i32, ParseError> {
+	Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+fn invalid(reason: &str) -> ParseError {
+    ParseError::InvalidBytes(reason.to_owned())
+}
+
+impl State {
+    /// Encode the state in the compact layout of the `binary` module,
+    /// read back by `from_bytes`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+	let mut bytes = vec![VERSION];
+	bytes.extend_from_slice(&self.hand_id().to_le_bytes());
+	let num_rounds = self.get_round() + 1;
+	bytes.push(num_rounds);
+	for round in 0..num_rounds {
+	    let num_actions = self.state_.numActions[round as usize];
+	    bytes.push(num_actions);
+	    for (_, _, action) in self.action_history().filter(|&(r, _, _)| r == round) {
+		match action {
+		    Action::Fold => bytes.push(0),
+		    Action::Call => bytes.push(1),
+		    Action::Raise(size) => {
+			bytes.push(2);
+			bytes.extend_from_slice(&size.to_le_bytes());
+		    },
+		    Action::Invalid => unreachable!("invalid actions are never taken"),
+		}
+	    }
+	}
+	for player in 0..self.game.number_of_players() {
+	    bytes.extend_from_slice(self.hole_cards(player).unwrap());
+	}
+	let board = self.board_cards();
+	bytes.push(board.len() as u8);
+	bytes.extend_from_slice(board);
+	bytes
+    }
+
+    /// Decode a state of `game` written by `to_bytes`.
+    ///
+    /// `InvalidBytes` is returned if the bytes are not in the layout, hold
+    /// trailing bytes, or the actions or cards are not valid in `game`.
+    pub fn from_bytes(game: &Game, bytes: &[u8]) -> Result<State, ParseError> {
+	let mut reader = Reader { bytes };
+	if reader.u8()? != VERSION {
+	    return Err(invalid("unsupported version"));
+	}
+	let hand_id = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+	let mut state = State::new_with_hand_id(game.clone(), hand_id);
+	let num_rounds = reader.u8()?;
+	if num_rounds == 0 || num_rounds > game.number_of_rounds() {
+	    return Err(invalid("invalid number of rounds"));
+	}
+	for round in 0..num_rounds {
+	    for _ in 0..reader.u8()? {
+		if state.get_round() != round {
+		    return Err(invalid("action in a round which is over"));
+		}
+		let action = match reader.u8()? {
+		    0 => Action::Fold,
+		    1 => Action::Call,
+		    2 => Action::Raise(reader.i32()?),
+		    _ => return Err(invalid("invalid action")),
+		};
+		state.do_action(action).map_err(|_| invalid("invalid action"))?;
+	    }
+	}
+	if state.get_round() + 1 != num_rounds {
+	    return Err(invalid("number of rounds does not match the actions"));
+	}
+	let num_hole_cards = game.num_hole_cards() as usize;
+	let hole_cards = (0..game.number_of_players())
+	    .map(|_| reader.take(num_hole_cards))
+	    .collect::<Result<Vec<_>, _>>()?;
+	let num_board_cards = reader.u8()? as usize;
+	if num_board_cards > acpc::MAX_BOARD_CARDS as usize {
+	    return Err(invalid("too many board cards"));
+	}
+	let board = reader.take(num_board_cards)?;
+	if board.contains(&NOT_DEALT) {
+	    return Err(invalid("invalid board card"));
+	}
+	if !reader.bytes.is_empty() {
+	    return Err(invalid("trailing bytes"));
+	}
+	// the dealt cards must be distinct cards of the deck, as the serde
+	// support checks
+	let dealt = hole_cards.iter().flat_map(|cards| cards.iter()).chain(board);
+	game.check_cards(dealt.filter(|&&c| c != NOT_DEALT)).map_err(|e| invalid(&e.to_string()))?;
+	for (player, cards) in hole_cards.iter().enumerate() {
+	    state.state_.holeCards[player][..num_hole_cards].copy_from_slice(cards);
+	}
+	state.state_.boardCards[..num_board_cards].copy_from_slice(board);
+	Ok(state)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DealerRng;
+
+    fn assert_same(expected: &State, state: &State) {
+	assert_eq!(expected.hand_id(), state.hand_id());
+	assert_eq!(expected.get_round(), state.get_round());
+	assert_eq!(expected.spents(), state.spents());
+	assert_eq!(expected.action_history().collect::<Vec<_>>(),
+		   state.action_history().collect::<Vec<_>>());
+	assert_eq!(expected.board_cards(), state.board_cards());
+	for p in 0..expected.game.number_of_players() {
+	    assert_eq!(expected.hole_cards(p), state.hole_cards(p));
+	}
+    }
+
+    #[test]
+    fn round_trip() {
+	let game = Game::holdem_nolimit_3p();
+	let mut state = State::new_with_hand_id(game.clone(), 1234);
+	assert_same(&state, &State::from_bytes(&game, &state.to_bytes()).unwrap());
+	state.deal_cards(&mut DealerRng::new(3));
+	for action in [Action::Raise(300), Action::Call, Action::Call, Action::Call, Action::Raise(1000)] {
+	    state.do_action(action).unwrap();
+	}
+	state.deal_cards(&mut DealerRng::new(3));
+	let bytes = state.to_bytes();
+	assert!(bytes.len() <= 32, "{} bytes", bytes.len());
+	assert_same(&state, &State::from_bytes(&game, &bytes).unwrap());
+
+	let game = Game::leduc();
+	let mut state = State::new(game.clone());
+	state.deal_cards(&mut DealerRng::new(5));
+	for action in [Action::Raise(2), Action::Call, Action::Call] {
+	    state.do_action(action).unwrap();
+	}
+	assert_same(&state, &State::from_bytes(&game, &state.to_bytes()).unwrap());
+    }
+
+    #[test]
+    fn invalid() {
+	let game = Game::holdem_nolimit_3p();
+	let mut state = State::new(game.clone());
+	state.do_action(Action::Raise(300)).unwrap();
+	let bytes = state.to_bytes();
+	let is_invalid = |bytes: &[u8]| matches!(State::from_bytes(&game, bytes), Err(ParseError::InvalidBytes(_)));
+	assert!(is_invalid(&bytes[..bytes.len() - 1]));
+	assert!(is_invalid(&[bytes.as_slice(), &[0]].concat()));
+	let mut other = bytes.clone();
+	other[0] = 2;
+	assert!(is_invalid(&other));
+	// a raise to 100 instead of 300 is too small
+	let mut other = bytes.clone();
+	other[8] = 100;
+	other[9] = 0;
+	assert!(is_invalid(&other));
+	assert!(is_invalid(&[]));
+
+	let game = Game::leduc();
+	let mut state = State::new(game.clone());
+	state.deal_cards(&mut DealerRng::new(5));
+	let bytes = state.to_bytes();
+	let holes = bytes.len() - 3;
+	assert!(State::from_bytes(&game, &bytes).is_ok());
+	let is_invalid = |bytes: &[u8]| matches!(State::from_bytes(&game, bytes), Err(ParseError::InvalidBytes(_)));
+	// both players hold the same card
+	let mut other = bytes.clone();
+	other[holes + 1] = other[holes];
+	assert!(is_invalid(&other));
+	// the deuce of clubs is not in the Leduc deck
+	let mut other = bytes.clone();
+	other[holes] = 0;
+	assert!(is_invalid(&other));
+    }
+}
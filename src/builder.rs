@@ -0,0 +1,275 @@
+use acpc_server_sys as acpc;
+
+use crate::{AcpcError, BettingType, Game};
+
+
+/// Builder for a `Game` defined in code instead of a `.game` file.
+///
+/// Players and rounds are 0-indexed as everywhere else in this crate, so
+/// unlike the `.game` format `first_player` starts with player 0. Values
+/// which are not set default to those of `readGame`: stacks of
+/// `i32::MAX`, player 0 first in each round and no limit of raises.
+#[derive(Debug, Clone)]
+pub struct GameBuilder {
+    betting_type: BettingType,
+    num_players: u8,
+    num_rounds: u8,
+    stack: Option<Vec<i32>>,
+    blind: Vec<i32>,
+    raise_size: Option<Vec<i32>>,
+    first_player: Option<Vec<u8>>,
+    max_raises: Option<Vec<u8>>,
+    num_suits: u8,
+    num_ranks: u8,
+    num_hole_cards: u8,
+    num_board_cards: Vec<u8>,
+}
+
+impl GameBuilder {
+    pub fn new(betting_type: BettingType, num_players: u8, num_rounds: u8) -> Self {
+	GameBuilder {
+	    betting_type,
+	    num_players,
+	    num_rounds,
+	    stack: None,
+	    blind: vec![],
+	    raise_size: None,
+	    first_player: None,
+	    max_raises: None,
+	    num_suits: 0,
+	    num_ranks: 0,
+	    num_hole_cards: 0,
+	    num_board_cards: vec![],
+	}
+    }
+
+    /// Stack of each player.
+    pub fn stack(mut self, stack: &[i32]) -> Self {
+	self.stack = Some(stack.to_vec());
+	self
+    }
+
+    /// Blind of each player.
+    pub fn blind(mut self, blind: &[i32]) -> Self {
+	self.blind = blind.to_vec();
+	self
+    }
+
+    /// Size of the fixed raises of each round, needed by limit games.
+    pub fn raise_size(mut self, raise_size: &[i32]) -> Self {
+	self.raise_size = Some(raise_size.to_vec());
+	self
+    }
+
+    /// The player who acts first in each round.
+    pub fn first_player(mut self, first_player: &[u8]) -> Self {
+	self.first_player = Some(first_player.to_vec());
+	self
+    }
+
+    /// Maximum number of bets and raises in each round.
+    pub fn max_raises(mut self, max_raises: &[u8]) -> Self {
+	self.max_raises = Some(max_raises.to_vec());
+	self
+    }
+
+    /// Number of suits and ranks of the deck.
+    pub fn deck(mut self, num_suits: u8, num_ranks: u8) -> Self {
+	self.num_suits = num_suits;
+	self.num_ranks = num_ranks;
+	self
+    }
+
+    pub fn num_hole_cards(mut self, num_hole_cards: u8) -> Self {
+	self.num_hole_cards = num_hole_cards;
+	self
+    }
+
+    /// Number of board cards dealt in each round.
+    pub fn num_board_cards(mut self, num_board_cards: &[u8]) -> Self {
+	self.num_board_cards = num_board_cards.to_vec();
+	self
+    }
+
+    /// Build the game, checking the same constraints as `readGame`.
+    pub fn build(&self) -> Result<Game, AcpcError> {
+	let num_players = self.num_players as usize;
+	let num_rounds = self.num_rounds as usize;
+	if num_players < 2 || num_players > acpc::MAX_PLAYERS as usize {
+	    return Err(invalid(format!("number of players {}", num_players)));
+	}
+	if num_rounds == 0 || num_rounds > acpc::MAX_ROUNDS as usize {
+	    return Err(invalid(format!("number of rounds {}", num_rounds)));
+	}
+	let stack = self.stack.clone().unwrap_or_else(|| vec![i32::MAX; num_players]);
+	let raise_size = match (&self.raise_size, self.betting_type) {
+	    (Some(raise_size), _) => raise_size.clone(),
+	    (None, BettingType::Limit) => {
+		return Err(invalid("raise sizes are needed by limit games".to_owned()));
+	    }
+	    (None, BettingType::NoLimit) => vec![0; num_rounds],
+	};
+	let first_player = self.first_player.clone().unwrap_or_else(|| vec![0; num_rounds]);
+	let max_raises = self.max_raises.clone().unwrap_or_else(|| vec![u8::MAX; num_rounds]);
+	check_len("stack", &stack, num_players)?;
+	check_len("blind", &self.blind, num_players)?;
+	check_len("raise_size", &raise_size, num_rounds)?;
+	check_len("first_player", &first_player, num_rounds)?;
+	check_len("max_raises", &max_raises, num_rounds)?;
+	check_len("num_board_cards", &self.num_board_cards, num_rounds)?;
+
+	let mut game_ = acpc::Game {
+	    stack: [0; acpc::MAX_PLAYERS as usize],
+	    blind: [0; acpc::MAX_PLAYERS as usize],
+	    raiseSize: [0; acpc::MAX_ROUNDS as usize],
+	    bettingType: match self.betting_type {
+		BettingType::Limit => acpc::BettingType_limitBetting,
+		BettingType::NoLimit => acpc::BettingType_noLimitBetting,
+	    },
+	    numPlayers: self.num_players,
+	    numRounds: self.num_rounds,
+	    firstPlayer: [0; acpc::MAX_ROUNDS as usize],
+	    maxRaises: [0; acpc::MAX_ROUNDS as usize],
+	    numSuits: self.num_suits,
+	    numRanks: self.num_ranks,
+	    numHoleCards: self.num_hole_cards,
+	    numBoardCards: [0; acpc::MAX_ROUNDS as usize],
+	};
+	game_.stack[..num_players].copy_from_slice(&stack);
+	game_.blind[..num_players].copy_from_slice(&self.blind);
+	game_.raiseSize[..num_rounds].copy_from_slice(&raise_size);
+	game_.firstPlayer[..num_rounds].copy_from_slice(&first_player);
+	game_.maxRaises[..num_rounds].copy_from_slice(&max_raises);
+	game_.numBoardCards[..num_rounds].copy_from_slice(&self.num_board_cards);
+	validate(&game_)?;
+	Ok(Game { hand_id: 0, game_ })
+    }
+}
+
+fn invalid(reason: String) -> AcpcError {
+    AcpcError::InvalidGame(reason)
+}
+
+fn check_len<T>(name: &str, values: &[T], len: usize) -> Result<(), AcpcError> {
+    if values.len() != len {
+	Err(invalid(format!("{} needs {} values, got {}", name, len, values.len())))
+    } else {
+	Ok(())
+    }
+}
+
+/// Check the sanity checks of `readGame` on an already filled struct.
+pub(crate) fn validate(game_: &acpc::Game) -> Result<(), AcpcError> {
+    let num_players = game_.numPlayers as usize;
+    let num_rounds = game_.numRounds as usize;
+    if num_players < 2 || num_players > acpc::MAX_PLAYERS as usize {
+	return Err(invalid(format!("number of players {}", num_players)));
+    }
+    if num_rounds == 0 || num_rounds > acpc::MAX_ROUNDS as usize {
+	return Err(invalid(format!("number of rounds {}", num_rounds)));
+    }
+    for p in 0..num_players {
+	if game_.stack[p] < 0 {
+	    return Err(invalid(format!("negative stack of player {}", p)));
+	}
+	if game_.blind[p] < 0 {
+	    return Err(invalid(format!("negative blind of player {}", p)));
+	}
+	if game_.blind[p] > game_.stack[p] {
+	    return Err(invalid(format!("blind of player {} is greater than the stack", p)));
+	}
+    }
+    for r in 0..num_rounds {
+	if game_.firstPlayer[r] as usize >= num_players {
+	    return Err(invalid(format!("first player {} of round {}", game_.firstPlayer[r], r)));
+	}
+    }
+    if game_.numSuits == 0 || game_.numSuits as u32 > acpc::MAX_SUITS {
+	return Err(invalid(format!("number of suits {}", game_.numSuits)));
+    }
+    if game_.numRanks == 0 || game_.numRanks as u32 > acpc::MAX_RANKS {
+	return Err(invalid(format!("number of ranks {}", game_.numRanks)));
+    }
+    if game_.numHoleCards == 0 || game_.numHoleCards as u32 > acpc::MAX_HOLE_CARDS {
+	return Err(invalid(format!("number of hole cards {}", game_.numHoleCards)));
+    }
+    let num_board_cards = game_.numBoardCards[..num_rounds].iter()
+	.map(|&n| n as usize)
+	.sum::<usize>();
+    if num_board_cards > acpc::MAX_BOARD_CARDS as usize {
+	return Err(invalid(format!("{} board cards", num_board_cards)));
+    }
+    let num_cards = game_.numHoleCards as usize * num_players + num_board_cards;
+    if num_cards > game_.numSuits as usize * game_.numRanks as usize {
+	return Err(invalid("too many hole and board cards for the deck".to_owned()));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    fn leduc() -> GameBuilder {
+	GameBuilder::new(BettingType::Limit, 2, 2)
+	    .blind(&[1, 1])
+	    .raise_size(&[2, 4])
+	    .max_raises(&[2, 2])
+	    .deck(2, 3)
+	    .num_hole_cards(1)
+	    .num_board_cards(&[0, 1])
+    }
+
+    #[test]
+    fn build() {
+	let game = leduc().build().unwrap();
+	let expected = Game::read(File::open("resources/leduc.limit.2p.game").unwrap());
+	assert_eq!(expected, game);
+	assert_eq!(Ok(i32::MAX), game.stack_size(0));
+    }
+
+    #[test]
+    fn build_nolimit() {
+	let game = GameBuilder::new(BettingType::NoLimit, 2, 4)
+	    .stack(&[20000, 20000])
+	    .blind(&[100, 50])
+	    .first_player(&[1, 0, 0, 0])
+	    .deck(4, 13)
+	    .num_hole_cards(2)
+	    .num_board_cards(&[0, 3, 1, 1])
+	    .build()
+	    .unwrap();
+	assert_eq!(BettingType::NoLimit, game.betting_type());
+	assert_eq!(Ok(50), game.blind_size(1));
+	assert_eq!(Ok(1), game.first_player(0));
+	assert_eq!(Ok(u8::MAX), game.max_raises(3));
+	assert_eq!(40000, game.total_money());
+    }
+
+    #[test]
+    fn build_invalid() {
+	assert!(matches!(GameBuilder::new(BettingType::Limit, 1, 2).build(),
+			 Err(AcpcError::InvalidGame(_))));
+	assert!(GameBuilder::new(BettingType::Limit, 11, 2).build().is_err());
+	assert!(GameBuilder::new(BettingType::Limit, 2, 5).build().is_err());
+	assert!(leduc().blind(&[1, 1, 1]).build().is_err());
+	assert!(leduc().stack(&[1, 1]).blind(&[1, 2]).build().is_err());
+	assert!(leduc().num_board_cards(&[0]).build().is_err());
+	assert!(leduc().first_player(&[0, 2]).build().is_err());
+	assert!(leduc().deck(5, 3).build().is_err());
+	assert!(leduc().deck(1, 3).num_board_cards(&[0, 2]).build().is_err());
+	assert!(leduc().num_hole_cards(0).build().is_err());
+	assert_eq!(Err(AcpcError::InvalidGame("negative blind of player 0".to_owned())),
+		   leduc().blind(&[-1, 1]).build());
+	let nolimit = GameBuilder::new(BettingType::NoLimit, 2, 2)
+	    .blind(&[1, 1])
+	    .deck(2, 3)
+	    .num_hole_cards(1)
+	    .num_board_cards(&[0, 1]);
+	assert!(nolimit.build().is_ok());
+	let limit = GameBuilder { betting_type: BettingType::Limit, ..nolimit };
+	assert!(limit.build().is_err());
+    }
+}
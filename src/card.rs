@@ -0,0 +1,125 @@
+use acpc_server_sys as acpc;
+
+use std::error;
+use std::fmt;
+
+use crate::Card;
+
+
+const RANK_CHARS: &[u8] = b"23456789TJQKA";
+const SUIT_CHARS: &[u8] = b"cdhs";
+
+/// Error returned when a card string can not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CardError {
+    /// The string is not a rank character followed by a suit character.
+    InvalidFormat(String),
+
+    /// Unknown rank character.
+    InvalidRank(char),
+
+    /// Unknown suit character.
+    InvalidSuit(char),
+}
+
+impl fmt::Display for CardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	match self {
+	    CardError::InvalidFormat(s) => write!(f, "Invalid card {:?}, expected rank and suit like \"As\"", s),
+	    CardError::InvalidRank(c) => write!(f, "Invalid rank {:?}, expected one of 23456789TJQKA", c),
+	    CardError::InvalidSuit(c) => write!(f, "Invalid suit {:?}, expected one of cdhs", c),
+	}
+    }
+}
+
+impl error::Error for CardError {}
+
+/// Parse a card in ACPC notation such as `"As"`, `"Td"` or `"2c"`.
+///
+/// Cards are encoded as `rank * MAX_SUITS + suit`, the same as `makeCard`
+/// in game.c.
+pub fn parse_card(s: &str) -> Result<Card, CardError> {
+    let chars = s.chars().collect::<Vec<_>>();
+    if chars.len() != 2 {
+	return Err(CardError::InvalidFormat(s.to_owned()));
+    }
+    let rank = RANK_CHARS.iter()
+	.position(|&c| c as char == chars[0].to_ascii_uppercase())
+	.ok_or(CardError::InvalidRank(chars[0]))?;
+    let suit = SUIT_CHARS.iter()
+	.position(|&c| c as char == chars[1].to_ascii_lowercase())
+	.ok_or(CardError::InvalidSuit(chars[1]))?;
+    Ok((rank * acpc::MAX_SUITS as usize + suit) as Card)
+}
+
+/// Parse a list of cards separated by whitespace or commas.
+///
+/// Cards may also be written without separators as in MATCHSTATE lines,
+/// e.g. `"AsKh, 7h 8d"`.
+pub fn parse_cards(s: &str) -> Result<Vec<Card>, CardError> {
+    let mut cards = vec![];
+    for token in s.split(|c: char| c.is_whitespace() || c == ',') {
+	let chars = token.chars().collect::<Vec<_>>();
+	if chars.len() % 2 != 0 {
+	    return Err(CardError::InvalidFormat(token.to_owned()));
+	}
+	for pair in chars.chunks(2) {
+	    cards.push(parse_card(&pair.iter().collect::<String>())?);
+	}
+    }
+    Ok(cards)
+}
+
+/// Render a card in ACPC notation, the inverse of `parse_card`.
+///
+/// Values which do not encode a card are rendered as `"??"`.
+pub fn card_to_string(card: Card) -> String {
+    let rank = (card / acpc::MAX_SUITS as u8) as usize;
+    let suit = (card % acpc::MAX_SUITS as u8) as usize;
+    if rank >= RANK_CHARS.len() {
+	return "??".to_owned();
+    }
+    format!("{}{}", RANK_CHARS[rank] as char, SUIT_CHARS[suit] as char)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+	assert_eq!(Ok(0), parse_card("2c"));
+	assert_eq!(Ok(17), parse_card("6d"));
+	assert_eq!(Ok(34), parse_card("Th"));
+	assert_eq!(Ok(46), parse_card("Kh"));
+	assert_eq!(Ok(51), parse_card("As"));
+	assert_eq!(Ok(51), parse_card("aS"));
+    }
+
+    #[test]
+    fn parse_invalid() {
+	assert_eq!(Err(CardError::InvalidRank('X')), parse_card("Xs"));
+	assert_eq!(Err(CardError::InvalidRank('1')), parse_card("1h"));
+	assert_eq!(Err(CardError::InvalidSuit('x')), parse_card("Ax"));
+	assert_eq!(Err(CardError::InvalidFormat("".to_owned())), parse_card(""));
+	assert_eq!(Err(CardError::InvalidFormat("10h".to_owned())), parse_card("10h"));
+    }
+
+    #[test]
+    fn parse_list() {
+	assert_eq!(Ok(vec![17, 19, 23]), parse_cards("6d 6s 7s"));
+	assert_eq!(Ok(vec![17, 19, 23]), parse_cards("6d,6s, 7s"));
+	assert_eq!(Ok(vec![51, 46, 17]), parse_cards("AsKh 6d"));
+	assert_eq!(Ok(vec![]), parse_cards(""));
+	assert!(parse_cards("As K").is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+	for card in 0..52 {
+	    assert_eq!(Ok(card), parse_card(&card_to_string(card)));
+	}
+	assert_eq!("??", card_to_string(255));
+    }
+}
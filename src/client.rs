@@ -0,0 +1,194 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+use crate::{Action, Game, ParseError, State};
+
+
+/// Version of the ACPC protocol sent on connecting.
+const PROTOCOL_VERSION: &str = "VERSION:2.0.0";
+
+/// Error returned by `DealerClient`.
+#[derive(Debug)]
+pub enum ClientError {
+    /// Reading from or writing to the dealer failed.
+    Io(io::Error),
+
+    /// The dealer sent a line which is not a valid MATCHSTATE line.
+    Parse(ParseError),
+
+    /// An action was sent before a MATCHSTATE line was received.
+    NoMatchState,
+
+    /// `Action::Invalid` was sent, which has no ACPC token.
+    InvalidAction,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	match self {
+	    ClientError::Io(e) => e.fmt(f),
+	    ClientError::Parse(e) => e.fmt(f),
+	    ClientError::NoMatchState => write!(f, "No MATCHSTATE line to respond to"),
+	    ClientError::InvalidAction => write!(f, "Invalid action sent to the dealer"),
+	}
+    }
+}
+
+impl error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+	match self {
+	    ClientError::Io(e) => Some(e),
+	    ClientError::Parse(e) => Some(e),
+	    ClientError::NoMatchState | ClientError::InvalidAction => None,
+	}
+    }
+}
+
+impl From<io::Error> for ClientError {
+    fn from(e: io::Error) -> Self {
+	ClientError::Io(e)
+    }
+}
+
+impl From<ParseError> for ClientError {
+    fn from(e: ParseError) -> Self {
+	ClientError::Parse(e)
+    }
+}
+
+
+/// Client of the ACPC dealer which plays one seat of a match over TCP.
+#[derive(Debug)]
+pub struct DealerClient {
+    game: Game,
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    last_line: Option<String>,
+}
+
+impl DealerClient {
+    /// Connect to the dealer at `addr` and send the protocol version.
+    pub fn connect<A: ToSocketAddrs>(game: Game, addr: A) -> io::Result<Self> {
+	let writer = TcpStream::connect(addr)?;
+	writer.set_nodelay(true)?;
+	let reader = BufReader::new(writer.try_clone()?);
+	let mut client = DealerClient { game, reader, writer, last_line: None };
+	client.write_line(PROTOCOL_VERSION)?;
+	Ok(client)
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+	self.writer.write_all(format!("{}\r\n", line).as_bytes())?;
+	self.writer.flush()
+    }
+
+    pub fn game(&self) -> &Game {
+	&self.game
+    }
+
+    /// Read the next MATCHSTATE line and return the hand id, the position
+    /// of this client and the state, or `None` when the dealer closes the
+    /// connection at the end of the match.
+    ///
+    /// Comment lines starting with `#` or `;` and blank lines are skipped.
+    pub fn next_match_state(&mut self) -> Result<Option<(u32, u8, State)>, ClientError> {
+	loop {
+	    let mut line = String::new();
+	    if self.reader.read_line(&mut line)? == 0 {
+		self.last_line = None;
+		return Ok(None);
+	    }
+	    let line = line.trim_end();
+	    if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+		continue;
+	    }
+	    // a line which fails to parse leaves nothing to respond to
+	    self.last_line = None;
+	    let match_state = State::from_match_state(&self.game, line)?;
+	    self.last_line = Some(line.to_owned());
+	    return Ok(Some(match_state));
+	}
+    }
+
+    /// Respond to the last MATCHSTATE line with `action`, which must not be
+    /// `Action::Invalid`.
+    pub fn send_action(&mut self, action: Action) -> Result<(), ClientError> {
+	if action == Action::Invalid {
+	    return Err(ClientError::InvalidAction);
+	}
+	let line = match &self.last_line {
+	    Some(line) => format!("{}:{}", line, action.to_acpc_token()),
+	    None => return Err(ClientError::NoMatchState),
+	};
+	self.write_line(&line)?;
+	Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn get_game() -> Game {
+	let file = File::open("resources/holdem.nolimit.2p.reverse_blinds.game").unwrap();
+	Game::read(file)
+    }
+
+    #[test]
+    fn play_against_mock_dealer() {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap();
+	let dealer = thread::spawn(move || {
+	    let (stream, _) = listener.accept().unwrap();
+	    let mut reader = BufReader::new(stream.try_clone().unwrap());
+	    let mut writer = stream;
+	    let mut received = vec![];
+	    let mut line = String::new();
+	    reader.read_line(&mut line).unwrap();
+	    received.push(line.clone());
+	    writer.write_all(b"# a comment\r\n\r\nMATCHSTATE:1:0::|TdAs\r\n").unwrap();
+	    line.clear();
+	    reader.read_line(&mut line).unwrap();
+	    received.push(line.clone());
+	    writer.write_all(b"MATCHSTATE:1:0:r300c/:|TdAs/2c3d4h\r\n").unwrap();
+	    received
+	});
+
+	let mut client = DealerClient::connect(get_game(), addr).unwrap();
+	assert!(matches!(client.send_action(Action::Call), Err(ClientError::NoMatchState)));
+	let (hand_id, viewer, state) = client.next_match_state().unwrap().unwrap();
+	assert_eq!((0, 1), (hand_id, viewer));
+	assert_eq!(viewer, state.current_player());
+	assert!(matches!(client.send_action(Action::Invalid), Err(ClientError::InvalidAction)));
+	client.send_action(Action::Raise(300)).unwrap();
+	let (_, _, state) = client.next_match_state().unwrap().unwrap();
+	assert_eq!(1, state.get_round());
+	assert_eq!(600, state.pot_size());
+	assert!(client.next_match_state().unwrap().is_none());
+
+	let received = dealer.join().unwrap();
+	assert_eq!(vec!["VERSION:2.0.0\r\n", "MATCHSTATE:1:0::|TdAs:r300\r\n"], received);
+    }
+
+    #[test]
+    fn invalid_line() {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap();
+	let dealer = thread::spawn(move || {
+	    let (mut stream, _) = listener.accept().unwrap();
+	    stream.write_all(b"MATCHSTATE:1:0::|TdAs\r\nHELLO\r\n").unwrap();
+	});
+	let mut client = DealerClient::connect(get_game(), addr).unwrap();
+	assert!(client.next_match_state().unwrap().is_some());
+	assert!(matches!(client.next_match_state(), Err(ClientError::Parse(_))));
+	assert!(matches!(client.send_action(Action::Call), Err(ClientError::NoMatchState)));
+	dealer.join().unwrap();
+    }
+}
@@ -0,0 +1,112 @@
+use crate::{AcpcError, Action, DealerRng, Game, State};
+
+
+/// Environment playing hands of a game one action at a time, in the way of
+/// reinforcement learning environments.
+///
+/// Cards are dealt with the generator of the environment whenever a state
+/// is a chance node, so every state returned has the cards of its round.
+#[derive(Debug, Clone)]
+pub struct Env {
+    game: Game,
+    state: State,
+    rng: DealerRng,
+}
+
+impl Env {
+    /// Create the environment and deal hand 0 with a generator seeded by
+    /// `seed`.
+    pub fn new(game: Game, seed: u32) -> Self {
+	let state = State::new_with_hand_id(game.clone(), 0);
+	let mut env = Env { game, state, rng: DealerRng::new(seed) };
+	env.deal();
+	env
+    }
+
+    fn deal(&mut self) {
+	if self.state.is_chance_node() || self.state.is_finished() {
+	    self.state.deal_cards(&mut self.rng);
+	}
+    }
+
+    pub fn game(&self) -> &Game {
+	&self.game
+    }
+
+    pub fn state(&self) -> &State {
+	&self.state
+    }
+
+    /// Start the next hand and return its state.
+    pub fn reset(&mut self) -> &State {
+	let hand_id = self.state.hand_id().wrapping_add(1);
+	self.state = State::new_with_hand_id(self.game.clone(), hand_id);
+	self.deal();
+	&self.state
+    }
+
+    /// Take `action` for the current player and return the next state, the
+    /// rewards of all players and whether the hand is finished.
+    ///
+    /// The rewards are the `values` of the hand once it is finished and 0
+    /// before that. Invalid actions, including any action once the hand is
+    /// finished, leave the state unchanged.
+    pub fn step(&mut self, action: Action) -> Result<(State, Vec<f64>, bool), AcpcError> {
+	self.state.do_action(action)?;
+	self.deal();
+	let done = self.state.is_finished();
+	let rewards = if done {
+	    self.state.values()?
+	} else {
+	    vec![0.0; self.game.number_of_players() as usize]
+	};
+	Ok((self.state.clone(), rewards, done))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+
+    fn get_env() -> Env {
+	let file = File::open("resources/holdem.nolimit.3p.game").unwrap();
+	Env::new(Game::read(file), 42)
+    }
+
+    #[test]
+    fn random_episodes() {
+	let mut env = get_env();
+	let mut rng = DealerRng::new(7);
+	for hand_id in 0..20 {
+	    assert_eq!(hand_id, env.state().hand_id());
+	    assert!(!env.state().is_chance_node());
+	    loop {
+		let actions = env.state().legal_actions();
+		let action = actions[rng.next_u32() as usize % actions.len()];
+		let (state, rewards, done) = env.step(action).unwrap();
+		assert!(!state.is_chance_node());
+		assert_eq!(3, rewards.len());
+		if done {
+		    assert!(state.is_finished());
+		    assert_eq!(Ok(rewards.clone()), state.values());
+		    assert_eq!(0.0, rewards.iter().sum::<f64>());
+		    break;
+		}
+		assert_eq!(vec![0.0; 3], rewards);
+	    }
+	    assert!(env.step(Action::Call).is_err());
+	    env.reset();
+	}
+    }
+
+    #[test]
+    fn seeded() {
+	let mut env = get_env();
+	let mut other = get_env();
+	assert_eq!(env.state().hole_cards(0), other.state().hole_cards(0));
+	assert_ne!(env.reset().hole_cards(0).unwrap(), get_env().state().hole_cards(0).unwrap());
+	assert_eq!(env.state().hole_cards(1), other.reset().hole_cards(1));
+    }
+}
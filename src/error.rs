@@ -0,0 +1,136 @@
+use std::error;
+use std::fmt;
+
+use crate::{card_to_string, Action, ActionError, Card, CardError, ParseError};
+
+
+/// Error returned by the fallible methods of `Game` and `State`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AcpcError {
+    /// The player index is not less than the number of players.
+    InvalidPlayer(u8),
+
+    /// The round index is not less than the number of rounds.
+    InvalidRound(u8),
+
+    /// The action is not valid in the current state.
+    InvalidAction(Action),
+
+    /// No action with the index has been taken in the round.
+    InvalidActionIndex(u8),
+
+    /// The hand is still being played.
+    GameNotFinished,
+
+    /// The current player is not allowed to raise.
+    CannotRaise,
+
+    /// Cards which are needed have not been dealt.
+    CardsNotDealt,
+
+    /// The number of cards does not match the game.
+    InvalidCardCount(usize),
+
+    /// The card is not in the deck of the game.
+    InvalidCard(Card),
+
+    /// The card is used more than once.
+    DuplicateCard(Card),
+
+    /// There are too many ways to deal the missing cards to enumerate.
+    TooManyCompletions(u64),
+
+    /// The C library failed to print a string.
+    PrintFailed,
+
+    /// Invalid card.
+    Card(CardError),
+
+    /// Invalid action token.
+    ActionToken(ActionError),
+
+    /// Invalid line of the ACPC protocol.
+    Parse(ParseError),
+
+    /// The game definition breaks a constraint of the C library.
+    InvalidGame(String),
+}
+
+impl fmt::Display for AcpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	match self {
+	    AcpcError::InvalidPlayer(p) => write!(f, "Invalid player index {}", p),
+	    AcpcError::InvalidRound(r) => write!(f, "Invalid round index {}", r),
+	    AcpcError::InvalidAction(a) => write!(f, "Invalid action {:?}", a),
+	    AcpcError::InvalidActionIndex(i) => write!(f, "Invalid action index {}", i),
+	    AcpcError::GameNotFinished => write!(f, "Game is not finished"),
+	    AcpcError::CannotRaise => write!(f, "Player can not raise now"),
+	    AcpcError::CardsNotDealt => write!(f, "Cards are not dealt"),
+	    AcpcError::InvalidCardCount(n) => write!(f, "Invalid number of cards {}", n),
+	    AcpcError::InvalidCard(c) => write!(f, "Card {} is not in the deck", card_to_string(*c)),
+	    AcpcError::DuplicateCard(c) => write!(f, "Card {} is used more than once", card_to_string(*c)),
+	    AcpcError::TooManyCompletions(n) => write!(f, "Too many ways to deal the cards {}", n),
+	    AcpcError::PrintFailed => write!(f, "Failed to print with the ACPC library"),
+	    AcpcError::Card(e) => e.fmt(f),
+	    AcpcError::ActionToken(e) => e.fmt(f),
+	    AcpcError::Parse(e) => e.fmt(f),
+	    AcpcError::InvalidGame(s) => write!(f, "Invalid game, {}", s),
+	}
+    }
+}
+
+impl error::Error for AcpcError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+	match self {
+	    AcpcError::Card(e) => Some(e),
+	    AcpcError::ActionToken(e) => Some(e),
+	    AcpcError::Parse(e) => Some(e),
+	    _ => None,
+	}
+    }
+}
+
+impl From<CardError> for AcpcError {
+    fn from(e: CardError) -> Self {
+	AcpcError::Card(e)
+    }
+}
+
+impl From<ActionError> for AcpcError {
+    fn from(e: ActionError) -> Self {
+	AcpcError::ActionToken(e)
+    }
+}
+
+impl From<ParseError> for AcpcError {
+    fn from(e: ParseError) -> Self {
+	AcpcError::Parse(e)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_card;
+
+    fn card_of(s: &str) -> Result<u8, AcpcError> {
+	Ok(parse_card(s)?)
+    }
+
+    #[test]
+    fn display() {
+	assert_eq!("Invalid player index 3", AcpcError::InvalidPlayer(3).to_string());
+	assert_eq!("Invalid action Raise(100)",
+		   AcpcError::InvalidAction(Action::Raise(100)).to_string());
+	assert_eq!("Game is not finished", AcpcError::GameNotFinished.to_string());
+    }
+
+    #[test]
+    fn from() {
+	assert_eq!(Ok(51), card_of("As"));
+	assert_eq!(Err(AcpcError::Card(CardError::InvalidRank('X'))), card_of("Xs"));
+	assert_eq!("Invalid rank 'X', expected one of 23456789TJQKA",
+		   card_of("Xs").unwrap_err().to_string());
+    }
+}
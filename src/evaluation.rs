@@ -0,0 +1,262 @@
+use crate::{AcpcError, Action, DealerRng, Game, State};
+
+
+/// Totals of the values won by each player over the hands of a match.
+///
+/// Players are the agents taking part in the match, which may sit in
+/// different seats from one hand to the next: `record_seated` takes the
+/// player sitting in each seat, and `record` is the case where player `i`
+/// sits in seat `i`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MatchResult {
+    totals: Vec<f64>,
+    totals_bb: Vec<f64>,
+    hands: Vec<u64>,
+    /// Hands of games with a big blind, which `totals_bb` is made of.
+    hands_bb: Vec<u64>,
+}
+
+impl MatchResult {
+    pub fn new() -> Self {
+	MatchResult::default()
+    }
+
+    /// Add the values of the finished hand `final_state`, player `i`
+    /// being in seat `i`.
+    pub fn record(&mut self, final_state: &State) -> Result<(), AcpcError> {
+	let players = (0..final_state.game.number_of_players()).collect::<Vec<_>>();
+	self.record_seated(final_state, &players)
+    }
+
+    /// Add the values of the finished hand `final_state`, where
+    /// `players[seat]` is the player in `seat`.
+    ///
+    /// `InvalidPlayer` is returned if `players` does not have exactly one
+    /// distinct player for each seat, with the number of players given
+    /// (at most 255) or the repeated player. Hands of games without blinds
+    /// are left out of `bb_per_100`.
+    pub fn record_seated(&mut self, final_state: &State, players: &[u8]) -> Result<(), AcpcError> {
+	if players.len() != final_state.game.number_of_players() as usize {
+	    return Err(AcpcError::InvalidPlayer(u8::try_from(players.len()).unwrap_or(u8::MAX)));
+	}
+	for (i, p) in players.iter().enumerate() {
+	    if players[..i].contains(p) {
+		return Err(AcpcError::InvalidPlayer(*p));
+	    }
+	}
+	let values = final_state.values()?;
+	let big_blind = final_state.game.blinds().iter().copied().max().unwrap_or(0);
+	let len = players.iter().map(|&p| p as usize + 1).max().unwrap_or(0);
+	if self.totals.len() < len {
+	    self.totals.resize(len, 0.0);
+	    self.totals_bb.resize(len, 0.0);
+	    self.hands.resize(len, 0);
+	    self.hands_bb.resize(len, 0);
+	}
+	for (seat, &p) in players.iter().enumerate() {
+	    let p = p as usize;
+	    self.totals[p] += values[seat];
+	    self.hands[p] += 1;
+	    if big_blind > 0 {
+		self.totals_bb[p] += values[seat] / big_blind as f64;
+		self.hands_bb[p] += 1;
+	    }
+	}
+	Ok(())
+    }
+
+    /// Total value won by `player`, in chips.
+    pub fn total(&self, player: u8) -> f64 {
+	self.totals.get(player as usize).copied().unwrap_or(0.0)
+    }
+
+    /// Number of hands `player` took part in.
+    pub fn hands(&self, player: u8) -> u64 {
+	self.hands.get(player as usize).copied().unwrap_or(0)
+    }
+
+    /// The big blinds won by `player` per 100 hands, the usual score of
+    /// agents, or 0 before any hand of the player with a big blind.
+    pub fn bb_per_100(&self, player: u8) -> f64 {
+	match self.hands_bb.get(player as usize).copied().unwrap_or(0) {
+	    0 => 0.0,
+	    hands => self.totals_bb[player as usize] / hands as f64 * 100.0,
+	}
+    }
+}
+
+
+/// A policy choosing the action of a player in a state.
+pub type Policy<'a> = &'a mut dyn FnMut(&State) -> Action;
+
+/// Duplicate match, where each hand is played once for every rotation of
+/// the players around the table with the same cards, so that the luck of
+/// the cards cancels out between the rotations.
+///
+/// The cards of a hand only depend on the seed and the hand id: seat `s`
+/// gets the same hole cards in every rotation.
+#[derive(Debug, Clone)]
+pub struct DuplicateMatch {
+    game: Game,
+    seed: u32,
+}
+
+impl DuplicateMatch {
+    pub fn new(game: Game, seed: u32) -> Self {
+	DuplicateMatch { game, seed }
+    }
+
+    pub fn game(&self) -> &Game {
+	&self.game
+    }
+
+    /// The rotations of the players, `seatings()[k][seat]` being the player
+    /// in `seat` in the rotation `k` as taken by `record_seated`.
+    pub fn seatings(&self) -> Vec<Vec<u8>> {
+	let n = self.game.number_of_players();
+	(0..n).map(|k| (0..n).map(|seat| (seat + k) % n).collect()).collect()
+    }
+
+    /// Play the hand `hand_id` for every rotation of `seatings`, where
+    /// `policies[player]` acts for `player`, and return the final states in
+    /// the order of the rotations.
+    ///
+    /// `InvalidPlayer` is returned with the number of policies if there is
+    /// not one per player, and invalid actions of a policy fail the hand.
+    pub fn play_hand(&self, hand_id: u32, policies: &mut [Policy]) -> Result<Vec<State>, AcpcError> {
+	if policies.len() != self.game.number_of_players() as usize {
+	    return Err(AcpcError::InvalidPlayer(u8::try_from(policies.len()).unwrap_or(u8::MAX)));
+	}
+	let mut states = vec![];
+	for seating in self.seatings() {
+	    let mut rng = DealerRng::from_seed64((self.seed as u64) << 32 | hand_id as u64);
+	    let mut state = State::new_with_hand_id(self.game.clone(), hand_id);
+	    loop {
+		if state.is_chance_node() || state.is_finished() {
+		    state.deal_cards(&mut rng);
+		}
+		if state.is_finished() {
+		    break;
+		}
+		let player = seating[state.current_player() as usize];
+		let action = (policies[player as usize])(&state);
+		state.do_action(action)?;
+	    }
+	    states.push(state);
+	}
+	Ok(states)
+    }
+
+    /// Play the hands `0..num_hands` like `play_hand` and total the values
+    /// of the players over all the rotations.
+    pub fn run(&self, num_hands: u32, policies: &mut [Policy]) -> Result<MatchResult, AcpcError> {
+	let mut result = MatchResult::new();
+	let seatings = self.seatings();
+	for hand_id in 0..num_hands {
+	    for (state, seating) in self.play_hand(hand_id, policies)?.iter().zip(&seatings) {
+		result.record_seated(state, seating)?;
+	    }
+	}
+	Ok(result)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BettingType, GameBuilder};
+
+    fn finished(actions: &[Action]) -> State {
+	let mut state = State::new(Game::holdem_nolimit_2p());
+	for &action in actions {
+	    state.do_action(action).unwrap();
+	}
+	state
+    }
+
+    #[test]
+    fn record() {
+	let mut result = MatchResult::new();
+	assert_eq!(0.0, result.bb_per_100(0));
+	// the small blind in seat 1 folds
+	result.record(&finished(&[Action::Fold])).unwrap();
+	result.record_seated(&finished(&[Action::Fold]), &[1, 0]).unwrap();
+	result.record(&finished(&[Action::Raise(300), Action::Fold])).unwrap();
+	assert_eq!(-100.0, result.total(0));
+	assert_eq!(100.0, result.total(1));
+	assert_eq!(3, result.hands(0));
+	assert_eq!(3, result.hands(1));
+	assert!((result.bb_per_100(1) - 100.0 / 3.0).abs() < 1e-9);
+	assert!((result.bb_per_100(0) + 100.0 / 3.0).abs() < 1e-9);
+	assert_eq!(0, result.hands(2));
+
+	let before = result.clone();
+	assert_eq!(Err(AcpcError::GameNotFinished), result.record(&finished(&[])));
+	assert_eq!(Err(AcpcError::InvalidPlayer(1)),
+		   result.record_seated(&finished(&[Action::Fold]), &[0]));
+	assert_eq!(Err(AcpcError::InvalidPlayer(0)),
+		   result.record_seated(&finished(&[Action::Fold]), &[0, 0]));
+	assert_eq!(before, result);
+	let players = (0..=255).collect::<Vec<u8>>();
+	let players = [players.as_slice(), &[0]].concat();
+	assert_eq!(Err(AcpcError::InvalidPlayer(u8::MAX)),
+		   result.record_seated(&finished(&[Action::Fold]), &players));
+    }
+
+    #[test]
+    fn record_without_blinds() {
+	let game = GameBuilder::new(BettingType::NoLimit, 2, 1)
+	    .stack(&[100, 100])
+	    .blind(&[0, 0])
+	    .deck(4, 13)
+	    .num_hole_cards(1)
+	    .num_board_cards(&[0])
+	    .build()
+	    .unwrap();
+	let mut state = State::new(game);
+	state.do_action(Action::Raise(10)).unwrap();
+	state.do_action(Action::Raise(20)).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	let mut result = MatchResult::new();
+	result.record(&state).unwrap();
+	assert_eq!(1, result.hands(0));
+	assert_eq!(-10.0, result.total(0));
+	assert_eq!(0.0, result.bb_per_100(0));
+	assert_eq!(0.0, result.bb_per_100(1));
+    }
+
+    #[test]
+    fn duplicate_pair() {
+	let duplicate = DuplicateMatch::new(Game::holdem_nolimit_2p(), 7);
+	assert_eq!(vec![vec![0, 1], vec![1, 0]], duplicate.seatings());
+	let mut seats = [vec![], vec![]];
+	{
+	    let [seats0, seats1] = &mut seats;
+	    let mut policy0 = |state: &State| { seats0.push(state.current_player()); Action::Call };
+	    let mut policy1 = |state: &State| { seats1.push(state.current_player()); Action::Call };
+	    let states = duplicate.play_hand(3, &mut [&mut policy0, &mut policy1]).unwrap();
+	    assert_eq!(2, states.len());
+	    for seat in 0..2 {
+		assert_eq!(states[0].hole_cards(seat), states[1].hole_cards(seat));
+	    }
+	    assert_eq!(5, states[0].board_cards().len());
+	    assert_eq!(states[0].board_cards(), states[1].board_cards());
+	    assert_eq!(states[0].values(), states[1].values());
+	    assert_ne!(states[0].hole_cards(0), duplicate.play_hand(4, &mut [&mut policy0, &mut policy1])
+		       .unwrap()[0].hole_cards(0));
+	}
+	// player 1 starts in seat 1, the small blind acting first, and both
+	// players sit in both seats
+	assert_eq!(1, seats[1][0]);
+	assert!(seats.iter().all(|s| s.contains(&0) && s.contains(&1)));
+
+	let mut call = |_: &State| Action::Call;
+	let mut call_too = |_: &State| Action::Call;
+	let result = duplicate.run(10, &mut [&mut call, &mut call_too]).unwrap();
+	assert_eq!(20, result.hands(0));
+	assert_eq!(0.0, result.total(0));
+	assert_eq!(0.0, result.total(1));
+	assert_eq!(Some(AcpcError::InvalidPlayer(1)), duplicate.play_hand(0, &mut [&mut call]).err());
+    }
+}
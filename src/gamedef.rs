@@ -0,0 +1,186 @@
+use std::str::FromStr;
+
+use crate::{BettingType, Game, GameBuilder, ParseError};
+
+
+/// Values of a key and the line they were read from.
+type Item<T> = Option<(usize, Vec<T>)>;
+
+fn invalid(line: usize, reason: String) -> ParseError {
+    ParseError::InvalidGameDef { line, reason }
+}
+
+fn parse_values<T: FromStr>(line: usize, key: &str, rest: &str) -> Result<Vec<T>, ParseError> {
+    let rest = rest.trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest);
+    let values = rest.split_whitespace()
+	.map(|s| s.parse().map_err(|_| invalid(line, format!("invalid value {:?} of {}", s, key))))
+	.collect::<Result<Vec<T>, _>>()?;
+    if values.is_empty() {
+	return Err(invalid(line, format!("{} needs a value", key)));
+    }
+    Ok(values)
+}
+
+fn parse_value<T: FromStr>(line: usize, key: &str, rest: &str) -> Result<T, ParseError> {
+    let mut values = parse_values(line, key, rest)?;
+    if values.len() != 1 {
+	return Err(invalid(line, format!("{} needs a single value", key)));
+    }
+    Ok(values.remove(0))
+}
+
+fn required<T>(value: Option<T>, end: usize, key: &str) -> Result<T, ParseError> {
+    value.ok_or_else(|| invalid(end, format!("{} is missing", key)))
+}
+
+fn check_len<T>(item: &Item<T>, key: &str, len: u8) -> Result<(), ParseError> {
+    match item {
+	Some((line, values)) if values.len() != len as usize => {
+	    Err(invalid(*line, format!("{} needs {} values, got {}", key, len, values.len())))
+	},
+	_ => Ok(()),
+    }
+}
+
+impl FromStr for Game {
+    type Err = ParseError;
+
+    /// Parse a game definition in the `.game` format read by `Game::read`.
+    ///
+    /// Keys are case insensitive as in `readGame`, but unknown keys and
+    /// malformed values are rejected with the line they appear on. Errors
+    /// about missing keys or the game as a whole point at the line which
+    /// ends the definition.
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+	let mut betting_type = BettingType::Limit;
+	let mut num_players = None;
+	let mut num_rounds = None;
+	let mut stack: Item<i32> = None;
+	let mut blind: Item<i32> = None;
+	let mut raise_size: Item<i32> = None;
+	let mut first_player: Item<u8> = None;
+	let mut max_raises: Item<u8> = None;
+	let mut num_suits = None;
+	let mut num_ranks = None;
+	let mut num_hole_cards = None;
+	let mut num_board_cards: Item<u8> = None;
+	let mut end = 0;
+	for (i, line) in s.lines().enumerate() {
+	    let line_no = i + 1;
+	    end = line_no;
+	    let line = line.trim();
+	    if line.is_empty() || line.starts_with('#') {
+		continue;
+	    }
+	    let lower = line.to_ascii_lowercase();
+	    if lower.starts_with("end gamedef") {
+		break;
+	    }
+	    let key_len = line.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(line.len());
+	    let (key, rest) = (&lower[..key_len], &line[key_len..]);
+	    match key {
+		"gamedef" => {},
+		"limit" => betting_type = BettingType::Limit,
+		"nolimit" => betting_type = BettingType::NoLimit,
+		"numplayers" => num_players = Some(parse_value(line_no, key, rest)?),
+		"numrounds" => num_rounds = Some(parse_value(line_no, key, rest)?),
+		"stack" => stack = Some((line_no, parse_values(line_no, key, rest)?)),
+		"blind" => blind = Some((line_no, parse_values(line_no, key, rest)?)),
+		"raisesize" => raise_size = Some((line_no, parse_values(line_no, key, rest)?)),
+		"firstplayer" => {
+		    let values: Vec<u8> = parse_values(line_no, key, rest)?;
+		    if values.contains(&0) {
+			return Err(invalid(line_no, "players of firstPlayer start at 1".to_owned()));
+		    }
+		    first_player = Some((line_no, values.iter().map(|p| p - 1).collect()));
+		},
+		"maxraises" => max_raises = Some((line_no, parse_values(line_no, key, rest)?)),
+		"numsuits" => num_suits = Some(parse_value(line_no, key, rest)?),
+		"numranks" => num_ranks = Some(parse_value(line_no, key, rest)?),
+		"numholecards" => num_hole_cards = Some(parse_value(line_no, key, rest)?),
+		"numboardcards" => {
+		    num_board_cards = Some((line_no, parse_values(line_no, key, rest)?));
+		},
+		_ => return Err(invalid(line_no, format!("unknown key {:?}", key))),
+	    }
+	}
+
+	let num_players = required(num_players, end, "numPlayers")?;
+	let num_rounds = required(num_rounds, end, "numRounds")?;
+	check_len(&stack, "stack", num_players)?;
+	check_len(&blind, "blind", num_players)?;
+	check_len(&raise_size, "raiseSize", num_rounds)?;
+	check_len(&first_player, "firstPlayer", num_rounds)?;
+	check_len(&max_raises, "maxRaises", num_rounds)?;
+	check_len(&num_board_cards, "numBoardCards", num_rounds)?;
+	let (_, blind) = required(blind, end, "blind")?;
+	let (_, num_board_cards) = required(num_board_cards, end, "numBoardCards")?;
+	let mut builder = GameBuilder::new(betting_type, num_players, num_rounds)
+	    .blind(&blind)
+	    .deck(required(num_suits, end, "numSuits")?, required(num_ranks, end, "numRanks")?)
+	    .num_hole_cards(required(num_hole_cards, end, "numHoleCards")?)
+	    .num_board_cards(&num_board_cards);
+	if let Some((_, stack)) = stack {
+	    builder = builder.stack(&stack);
+	}
+	if let Some((_, raise_size)) = raise_size {
+	    builder = builder.raise_size(&raise_size);
+	}
+	if let Some((_, first_player)) = first_player {
+	    builder = builder.first_player(&first_player);
+	}
+	if let Some((_, max_raises)) = max_raises {
+	    builder = builder.max_raises(&max_raises);
+	}
+	builder.build().map_err(|e| invalid(end, e.to_string()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+
+    const LEDUC: &str = include_str!("../resources/leduc.limit.2p.game");
+
+    #[test]
+    fn from_str() {
+	let expected = Game::read(File::open("resources/leduc.limit.2p.game").unwrap());
+	assert_eq!(expected, LEDUC.parse().unwrap());
+	for entry in fs::read_dir("resources").unwrap() {
+	    let path = entry.unwrap().path();
+	    if path.extension().and_then(|e| e.to_str()) != Some("game") {
+		continue;
+	    }
+	    let expected = Game::read(File::open(&path).unwrap());
+	    let text = fs::read_to_string(&path).unwrap();
+	    assert_eq!(expected, text.parse().unwrap(), "{}", path.display());
+	}
+    }
+
+    #[test]
+    fn comments_and_case() {
+	let text = format!("# leduc\n\n{}", LEDUC.to_uppercase());
+	assert_eq!(LEDUC.parse::<Game>(), text.parse());
+    }
+
+    #[test]
+    fn invalid() {
+	let line_of = |text: &str| match text.parse::<Game>() {
+	    Err(ParseError::InvalidGameDef { line, .. }) => line,
+	    result => panic!("{:?}", result),
+	};
+	assert_eq!(3, line_of(&LEDUC.replace("numPlayers = 2", "numPlayers = two")));
+	assert_eq!(3, line_of(&LEDUC.replace("numPlayers = 2", "numPlayer = 2")));
+	assert_eq!(3, line_of(&LEDUC.replace("numPlayers = 2", "numPlayers = 2 2")));
+	assert_eq!(5, line_of(&LEDUC.replace("blind = 1 1", "blind = 1 1 1")));
+	assert_eq!(7, line_of(&LEDUC.replace("firstPlayer = 1 1", "firstPlayer = 0 1")));
+	assert_eq!(12, line_of(&LEDUC.replace("numBoardCards = 0 1", "numBoardCards =")));
+	// errors which are not about a single line point at END GAMEDEF
+	assert_eq!(12, line_of(&LEDUC.replace("numRounds = 2\n", "")));
+	assert_eq!(13, line_of(&LEDUC.replace("firstPlayer = 1 1", "firstPlayer = 3 1")));
+	assert!(matches!("".parse::<Game>(), Err(ParseError::InvalidGameDef { .. })));
+    }
+}
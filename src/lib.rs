@@ -1,16 +1,43 @@
 use acpc_server_sys as acpc;
 use libc;
 
-use std::fs::File;
+use std::fs::{self, File};
 use std::os::unix::io::IntoRawFd;
-use std::ffi::CStr;
+use std::path::Path;
 use std::ffi::CString;
 use std::fmt;
+use std::io;
+use std::io::{BufRead, Read};
+
+mod abstraction;
+mod binary;
+mod builder;
+mod card;
+mod client;
+mod env;
+mod error;
+mod evaluation;
+mod gamedef;
+#[cfg(feature = "serde")]
+mod serialize;
+mod tree;
+pub use abstraction::{CardAbstraction, IdentityAbstraction};
+pub use builder::GameBuilder;
+pub use card::{card_to_string, parse_card, parse_cards, CardError};
+pub use client::{ClientError, DealerClient};
+pub use env::Env;
+pub use error::AcpcError;
+pub use evaluation::{DuplicateMatch, MatchResult, Policy};
+pub use tree::{GameTree, Node};
 
 
 pub type Card = u8;
 const NOT_DEALT: u8 = 255;
 
+/// Largest number of ways to deal the missing cards `exact_equity` goes
+/// through.
+const MAX_EXACT_COMPLETIONS: u64 = 1_000_000;
+
 /// Available actions in a game.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Action {
@@ -21,12 +48,202 @@ pub enum Action {
     Call,
 
     /// Raise action with a specified amount.
+    ///
+    /// In no-limit games the amount is the total the player has spent after
+    /// the raise, not the amount added to the pot: `Raise(200)` facing a big
+    /// blind of 100 raises by 100 *to* 200. Use `Action::raise_to` and
+    /// `Action::raise_by` to make the intent explicit. Limit games ignore
+    /// the amount.
     Raise(i32),
 
     /// Invalid
     Invalid,
 }
 
+/// Error returned when an ACPC action token can not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ActionError {
+    /// The token is empty.
+    Empty,
+
+    /// Unknown action character.
+    UnknownAction(char),
+
+    /// The raise size is not a non-negative integer.
+    InvalidSize(String),
+
+    /// Fold and call tokens can not be followed by anything.
+    InvalidToken(String),
+}
+
+impl fmt::Display for ActionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	match self {
+	    ActionError::Empty => write!(f, "Empty action token"),
+	    ActionError::UnknownAction(c) => write!(f, "Unknown action {:?}", c),
+	    ActionError::InvalidSize(s) => write!(f, "Invalid raise size {:?}", s),
+	    ActionError::InvalidToken(s) => write!(f, "Invalid action token {:?}", s),
+	}
+    }
+}
+
+impl std::error::Error for ActionError {}
+
+/// Error returned when a line of the ACPC protocol or of a game definition
+/// can not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The line is not a valid MATCHSTATE line for the game.
+    InvalidMatchState(String),
+
+    /// The line is not a valid STATE line of a dealer log for the game.
+    InvalidLogLine(String),
+
+    /// The game definition is invalid at the 1-indexed `line`.
+    InvalidGameDef { line: usize, reason: String },
+
+    /// The bytes are not a state written by `State::to_bytes`.
+    InvalidBytes(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	match self {
+	    ParseError::InvalidMatchState(s) => write!(f, "Invalid MATCHSTATE line {:?}", s),
+	    ParseError::InvalidLogLine(s) => write!(f, "Invalid STATE line {:?}", s),
+	    ParseError::InvalidGameDef { line, reason } => {
+		write!(f, "Invalid game definition at line {}: {}", line, reason)
+	    },
+	    ParseError::InvalidBytes(reason) => write!(f, "Invalid state bytes: {}", reason),
+	}
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl Default for Action {
+    /// Fold, a placeholder default; it is not legal when the player can
+    /// check.
+    fn default() -> Self {
+	Action::Fold
+    }
+}
+
+impl Action {
+    /// Fold, call and raise, for enumerating the kinds of actions.
+    ///
+    /// The size of the raise is a placeholder, to be filled in from the
+    /// state with e.g. `State::raise_size` in no-limit games.
+    pub const BASIC: [Action; 3] = [Action::Fold, Action::Call, Action::Raise(0)];
+
+    /// Raise so that the total spent by the player is `total`.
+    pub fn raise_to(total: i32) -> Action {
+	Action::Raise(total)
+    }
+
+    /// Raise by `delta` over the largest amount spent so far in `state`,
+    /// i.e. to `state.max_spend() + delta`.
+    pub fn raise_by(state: &State, delta: i32) -> Action {
+	Action::Raise(state.max_spend() + delta)
+    }
+
+    /// Parse an action token of the ACPC protocol such as `"f"`, `"c"` or
+    /// `"r200"`.
+    ///
+    /// The same characters as `readAction` in game.c are accepted.  A raise
+    /// without a size (`"r"`), which is how limit games raise, is parsed as
+    /// `Raise(0)`.
+    pub fn from_acpc_token(token: &str) -> Result<Action, ActionError> {
+	let mut chars = token.chars();
+	let kind = chars.next().ok_or(ActionError::Empty)?;
+	let rest = chars.as_str();
+	match kind {
+	    'f' | 'F' | 'c' | 'C' | 'k' | 'K' if !rest.is_empty() => {
+		Err(ActionError::InvalidToken(token.to_owned()))
+	    },
+	    'f' | 'F' => Ok(Action::Fold),
+	    'c' | 'C' | 'k' | 'K' => Ok(Action::Call),
+	    'r' | 'R' | 'b' | 'B' if rest.is_empty() => Ok(Action::Raise(0)),
+	    'r' | 'R' | 'b' | 'B' => {
+		if !rest.bytes().all(|b| b.is_ascii_digit()) {
+		    return Err(ActionError::InvalidSize(rest.to_owned()));
+		}
+		rest.parse::<i32>()
+		    .map(Action::Raise)
+		    .map_err(|_| ActionError::InvalidSize(rest.to_owned()))
+	    },
+	    c => Err(ActionError::UnknownAction(c)),
+	}
+    }
+
+    /// Render the action as an ACPC protocol token, as `printAction` in
+    /// game.c does.
+    ///
+    /// `Raise(0)` is the sizeless raise of limit games and is rendered as
+    /// `"r"`.  `Action::Invalid` has no token and is rendered as an empty
+    /// string.
+    pub fn to_acpc_token(&self) -> String {
+	match self {
+	    Action::Fold => "f".to_owned(),
+	    Action::Call => "c".to_owned(),
+	    Action::Raise(0) => "r".to_owned(),
+	    Action::Raise(size) => format!("r{}", size),
+	    Action::Invalid => String::new(),
+	}
+    }
+}
+
+/// Human readable form such as `"fold"`, `"call"` or `"raise to 200"`.
+///
+/// A raise without a size, as in limit games, is shown as `"raise"`. Use
+/// `to_acpc_token` for the protocol form.
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	match self {
+	    Action::Fold => write!(f, "fold"),
+	    Action::Call => write!(f, "call"),
+	    Action::Raise(0) => write!(f, "raise"),
+	    Action::Raise(size) => write!(f, "raise to {}", size),
+	    Action::Invalid => write!(f, "invalid"),
+	}
+    }
+}
+
+fn from_acpc_action(action: &acpc::Action) -> Action {
+    match action.type_ {
+	acpc::ActionType_a_fold => Action::Fold,
+	acpc::ActionType_a_call => Action::Call,
+	acpc::ActionType_a_raise => Action::Raise(action.size),
+	_ => Action::Invalid,
+    }
+}
+
+/// Upper limit of the buffer used by `print_to_string`.
+const MAX_PRINT_LEN: usize = 1 << 20;
+
+/// Call a C function printing to a string and convert the result.
+///
+/// The print functions of game.c return -1 when the string does not fit
+/// in `maxLen`, so the buffer is grown and the call retried.
+fn print_to_string<F>(print: F) -> Option<String>
+where
+    F: Fn(i32, *mut libc::c_char) -> i32,
+{
+    let mut max_len = acpc::MAX_LINE_LEN as usize;
+    loop {
+	let mut buf = vec![0u8; max_len];
+	let len = print(max_len as i32, buf.as_mut_ptr() as *mut libc::c_char);
+	if len >= 0 {
+	    buf.truncate(len as usize);
+	    return Some(String::from_utf8_lossy(&buf).into_owned());
+	}
+	if max_len >= MAX_PRINT_LEN {
+	    return None;
+	}
+	max_len *= 2;
+    }
+}
+
 fn to_acpc_action(action: &Action) -> acpc::Action {
     match action {
 	Action::Fold => acpc::Action{type_: acpc::ActionType_a_fold, size: 0},
@@ -37,6 +254,83 @@ fn to_acpc_action(action: &Action) -> acpc::Action {
 }
 
 
+/// Random number generator used by the ACPC dealer to shuffle cards.
+///
+/// Each generator owns its `rng_state_t` instead of sharing global state,
+/// so generators can be moved to other threads and deal independently.
+#[derive(Debug, Clone)]
+pub struct DealerRng {
+    rng_: acpc::rng_state_t,
+}
+
+impl DealerRng {
+    /// Initialize the generator with a seed, as the dealer does.
+    pub fn new(seed: u32) -> Self {
+	let mut rng_ = acpc::rng_state_t { mt: [0u32; 624usize], mti: 0 };
+	unsafe {
+	    acpc::init_genrand(&mut rng_ as *mut acpc::rng_state_t, seed);
+	}
+	DealerRng { rng_ }
+    }
+
+    /// Initialize the generator with a 64 bit seed, using both halves of
+    /// it as the key of `init_by_array`.
+    pub fn from_seed64(seed: u64) -> Self {
+	let mut rng_ = acpc::rng_state_t { mt: [0u32; 624usize], mti: 0 };
+	let mut key = [seed as u32, (seed >> 32) as u32];
+	unsafe {
+	    acpc::init_by_array(&mut rng_ as *mut acpc::rng_state_t,
+				key.as_mut_ptr(), key.len() as i32);
+	}
+	DealerRng { rng_ }
+    }
+
+    /// Generate a random number on [0, 0xffffffff].
+    pub fn next_u32(&mut self) -> u32 {
+	unsafe {
+	    acpc::genrand_int32(&mut self.rng_ as *mut acpc::rng_state_t)
+	}
+    }
+
+    /// Draw a card from the deck in the same way as `dealCard` in game.c.
+    fn deal_card(&mut self, deck: &mut Vec<Card>) -> Card {
+	let i = self.next_u32() as usize % deck.len();
+	deck.swap_remove(i)
+    }
+}
+
+
+/// Betting structure of a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BettingType {
+    /// Raises are of the fixed size `raiseSize` of the round, so the size
+    /// of `Action::Raise` is ignored.
+    Limit,
+
+    /// `Action::Raise(size)` raises the total amount spent to `size`.
+    NoLimit,
+}
+
+
+/// How a finished hand ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TerminalKind {
+    /// All players but `winner` folded.
+    FoldOut { winner: u8 },
+
+    /// Two or more players reached the showdown.
+    Showdown,
+}
+
+
+/// A game definition.
+///
+/// Games compare equal if they define the same game: the betting type, the
+/// number of players and rounds, the stacks and blinds of each player, the
+/// first player, maximum number of raises and number of board cards of
+/// each round, the raise sizes of each round of limit games, and the
+/// number of suits, ranks and hole cards. Unused array entries and the
+/// raise sizes of no-limit games are ignored.
 #[derive(Debug, Clone)]
 pub struct Game {
     hand_id: u32,
@@ -44,40 +338,153 @@ pub struct Game {
 }
 
 impl Game {
+    /// Read a game definition in the `.game` format from `file`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file is not a valid game definition, use
+    /// `Game::try_from` to handle the error instead.
     pub fn read(file: File) -> Self {
-	let hand_id = 0;
+	match Game::try_from(file) {
+	    Ok(game) => game,
+	    Err(e) => panic!("Failed to read the game: {}", e),
+	}
+    }
+
+    /// Read a game definition in the `.game` format from any reader, such
+    /// as the bytes of `include_str!`.
+    ///
+    /// The whole input is buffered and handed to `readGame` through an
+    /// in-memory `FILE`. An `InvalidData` error is returned if it is not a
+    /// valid game definition.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+	let mut bytes = vec![];
+	reader.read_to_end(&mut bytes)?;
 	let game_ = unsafe {
-	    let c_file = libc::fdopen(
-		file.into_raw_fd(),
-		CStr::from_bytes_with_nul_unchecked(b"r\0").as_ptr(),
-            );
-	    let game = acpc::readGame(c_file as *mut acpc::_IO_FILE);
-	    libc::fclose(c_file);
-	    *game
+	    let c_file = libc::fmemopen(
+		bytes.as_mut_ptr() as *mut libc::c_void,
+		bytes.len(),
+		c"r".as_ptr(),
+	    );
+	    if c_file.is_null() {
+		return Err(io::Error::last_os_error());
+	    }
+	    read_game(c_file)?
 	};
-	return Game { hand_id, game_ };
+	Ok(Game { hand_id: 0, game_ })
+    }
+
+    /// Read all the `.game` files in the directory `path`, sorted by name.
+    ///
+    /// Other files are skipped, and an invalid game definition fails the
+    /// whole read.
+    pub fn read_dir(path: &Path) -> io::Result<Vec<(String, Game)>> {
+	let mut games = vec![];
+	for entry in fs::read_dir(path)? {
+	    let path = entry?.path();
+	    if !path.is_file() || path.extension().and_then(|e| e.to_str()) != Some("game") {
+		continue;
+	    }
+	    let name = path.file_name().unwrap().to_string_lossy().into_owned();
+	    games.push((name, Game::read_from(File::open(&path)?)?));
+	}
+	games.sort_by(|a, b| a.0.cmp(&b.0));
+	Ok(games)
+    }
+
+    fn standard(definition: &str) -> Self {
+	definition.parse().expect("embedded game definitions are valid")
+    }
+
+    /// Leduc Hold'em with 2 players and limit betting.
+    pub fn leduc() -> Self {
+	Game::standard(include_str!("../resources/leduc.limit.2p.game"))
+    }
+
+    /// Heads-up limit Texas Hold'em of the ACPC, with reverse blinds.
+    pub fn holdem_limit_2p() -> Self {
+	Game::standard(include_str!("../resources/holdem.limit.2p.reverse_blinds.game"))
+    }
+
+    /// Heads-up no-limit Texas Hold'em of the ACPC, with reverse blinds.
+    pub fn holdem_nolimit_2p() -> Self {
+	Game::standard(include_str!("../resources/holdem.nolimit.2p.reverse_blinds.game"))
     }
 
+    /// 3-player no-limit Texas Hold'em.
+    pub fn holdem_nolimit_3p() -> Self {
+	Game::standard(include_str!("../resources/holdem.nolimit.3p.game"))
+    }
+
+    /// Write the game definition like `write`, failing if it cannot be
+    /// written.
     pub fn print(&self, file: File) {
+	if let Err(e) = self.write(file) {
+	    panic!("Failed to print the game: {}", e);
+	}
+    }
+
+    /// Write the game definition in the `.game` format read by `read` and
+    /// the official dealer. `file` is closed afterwards.
+    pub fn write(&self, file: File) -> io::Result<()> {
 	let game_ptr = &self.game_ as *const acpc::Game;
 	unsafe {
+	    let fd = file.into_raw_fd();
 	    let c_file = libc::fdopen(
-		file.into_raw_fd(),
-		CStr::from_bytes_with_nul_unchecked(b"r\0").as_ptr(),
+		fd,
+		c"w".as_ptr(),
             );
+	    if c_file.is_null() {
+		let err = io::Error::last_os_error();
+		libc::close(fd);
+		return Err(err);
+	    }
 	    acpc::printGame(c_file as *mut acpc::_IO_FILE, game_ptr);
+	    let failed = libc::ferror(c_file) != 0;
+	    if libc::fclose(c_file) != 0 || failed {
+		return Err(io::Error::last_os_error());
+	    }
 	}
+	Ok(())
+    }
+
+    /// Check the constraints of the C library on the game: the numbers of
+    /// players and rounds fit the fixed-size arrays, stacks and blinds are
+    /// not negative, and the cards dealt fit the board and the deck.
+    ///
+    /// Games from `GameBuilder`, `read` and `try_from` are always valid.
+    pub fn validate(&self) -> Result<(), AcpcError> {
+	builder::validate(&self.game_)
     }
 
     pub fn number_of_players(&self) -> u8 {
 	self.game_.numPlayers
     }
 
-    pub fn bc_start(&self, round: u8) -> u8 {
+    pub fn number_of_rounds(&self) -> u8 {
+	self.game_.numRounds
+    }
+
+    /// Iterate over the betting rounds of the game.
+    pub fn rounds(&self) -> std::ops::Range<u8> {
+	0..self.number_of_rounds()
+    }
+
+    pub fn betting_type(&self) -> BettingType {
+	match self.game_.bettingType {
+	    acpc::BettingType_limitBetting => BettingType::Limit,
+	    acpc::BettingType_noLimitBetting => BettingType::NoLimit,
+	    _ => panic!("Invalid betting type {}", self.game_.bettingType),
+	}
+    }
+
+    /// Index into the board cards where the cards of `round` begin.
+    pub fn bc_start(&self, round: u8) -> Result<u8, AcpcError> {
+	self.round_idx(round)?;
 	let game_ptr = &self.game_ as *const acpc::Game;
-	unsafe {
+	Ok(unsafe {
 	    acpc::bcStart(game_ptr, round)
-	}
+	})
     }
     
     pub fn sum_board_cards(&self, round: u8) -> u8 {
@@ -87,29 +494,265 @@ impl Game {
 	}
     }
 
-    fn player_idx(&self, player: u8) -> Result<usize, String> {
+    /// Number of board cards dealt at the start of `round` alone, unlike the
+    /// cumulative `sum_board_cards`.
+    pub fn board_cards_in_round(&self, round: u8) -> Result<u8, AcpcError> {
+	Ok(self.game_.numBoardCards[self.round_idx(round)?])
+    }
+
+    fn player_idx(&self, player: u8) -> Result<usize, AcpcError> {
 	if self.number_of_players() <= player {
-	    Err(format!("Invalid player index {}", player))
+	    Err(AcpcError::InvalidPlayer(player))
 	} else {
 	    Ok(player as usize)
 	}
     }
 
-    fn num_hole_cards(&self) -> u8 {
+    fn round_idx(&self, round: u8) -> Result<usize, AcpcError> {
+	if self.number_of_rounds() <= round {
+	    Err(AcpcError::InvalidRound(round))
+	} else {
+	    Ok(round as usize)
+	}
+    }
+
+    pub fn num_hole_cards(&self) -> u8 {
 	self.game_.numHoleCards
     }
 
-    pub fn stack_size(&self, player: u8) -> Result<i32, String> {
+    pub fn number_of_suits(&self) -> u8 {
+	self.game_.numSuits
+    }
+
+    pub fn number_of_ranks(&self) -> u8 {
+	self.game_.numRanks
+    }
+
+    /// The stacks of all the players.
+    pub fn stacks(&self) -> &[i32] {
+	&self.game_.stack[..self.number_of_players() as usize]
+    }
+
+    /// The blinds of all the players.
+    pub fn blinds(&self) -> &[i32] {
+	&self.game_.blind[..self.number_of_players() as usize]
+    }
+
+    pub fn stack_size(&self, player: u8) -> Result<i32, AcpcError> {
 	Ok(self.game_.stack[self.player_idx(player)?])
     }
 
-    pub fn blind_size(&self, player: u8) -> Result<i32, String> {
+    pub fn blind_size(&self, player: u8) -> Result<i32, AcpcError> {
 	Ok(self.game_.blind[self.player_idx(player)?])
     }
 
+    /// Size of the fixed raises in `round` of a limit game.
+    pub fn raise_size_for_round(&self, round: u8) -> Result<i32, AcpcError> {
+	Ok(self.game_.raiseSize[self.round_idx(round)?])
+    }
+
+    /// The player who acts first in `round`.
+    pub fn first_player(&self, round: u8) -> Result<u8, AcpcError> {
+	Ok(self.game_.firstPlayer[self.round_idx(round)?])
+    }
+
+    /// The seat of the button in `round`, the player who acts last when
+    /// nobody folds, i.e. right before `first_player` in seat order.
+    ///
+    /// In the usual games the button of the rounds after the first is the
+    /// dealer, while before the flop it is the big blind.
+    pub fn button_seat(&self, round: u8) -> Result<u8, AcpcError> {
+	let num_players = self.number_of_players();
+	Ok((self.first_player(round)? + num_players - 1) % num_players)
+    }
+
+    /// Maximum number of bets and raises in `round`.
+    pub fn max_raises(&self, round: u8) -> Result<u8, AcpcError> {
+	Ok(self.game_.maxRaises[self.round_idx(round)?])
+    }
+
     pub fn total_money(&self) -> i64 {
-	let n = self.number_of_players() as usize;
-	self.game_.stack.iter().take(n).fold(0, |sum, i| sum + (*i as i64))
+	self.stacks().iter().fold(0, |sum, i| sum + (*i as i64))
+    }
+
+    /// All the cards of the deck, ordered by suit and then by rank as
+    /// `dealCard` in game.c orders them.
+    pub fn full_deck(&self) -> Vec<Card> {
+	let max_suits = acpc::MAX_SUITS as u8;
+	let max_ranks = acpc::MAX_RANKS as u8;
+	((max_suits - self.game_.numSuits)..max_suits)
+	    .flat_map(|s| ((max_ranks - self.game_.numRanks)..max_ranks).map(move |r| r * max_suits + s))
+	    .collect()
+    }
+
+    fn in_deck(&self, card: Card) -> bool {
+	let rank = card / acpc::MAX_SUITS as u8;
+	let suit = card % acpc::MAX_SUITS as u8;
+	rank < acpc::MAX_RANKS as u8
+	    && rank >= acpc::MAX_RANKS as u8 - self.game_.numRanks
+	    && suit >= acpc::MAX_SUITS as u8 - self.game_.numSuits
+    }
+
+    /// Check that `cards` are distinct cards of the deck of this game.
+    fn check_cards<'a>(&self, cards: impl IntoIterator<Item = &'a Card>) -> Result<(), AcpcError> {
+	let mut used = 0u64;
+	for &card in cards {
+	    if !self.in_deck(card) {
+		return Err(AcpcError::InvalidCard(card));
+	    }
+	    if used & (1 << card) != 0 {
+		return Err(AcpcError::DuplicateCard(card));
+	    }
+	    used |= 1 << card;
+	}
+	Ok(())
+    }
+
+    /// Rank the best hand made from `hole` and `board`, where a larger rank
+    /// is a better hand. Ranks of hands with any number of cards can be
+    /// compared, e.g. Hold'em hands of 7 cards.
+    pub fn rank_hand(&self, hole: &[Card], board: &[Card]) -> Result<u32, AcpcError> {
+	self.check_cards(hole.iter().chain(board))?;
+	let cards = [hole, board].concat();
+	let rank = unsafe {
+	    acpc::rankCards(cards.as_ptr(), cards.len() as u8)
+	};
+	Ok(rank as u32)
+    }
+
+    /// Rank the hand of every player as if all of `holes` went to showdown
+    /// on `board`, with the ranks of `rank_hand`. No card may appear twice.
+    pub fn showdown_ranks(&self, holes: &[&[Card]], board: &[Card]) -> Result<Vec<u32>, AcpcError> {
+	self.check_cards(holes.iter().flat_map(|h| h.iter()).chain(board))?;
+	holes.iter().map(|hole| self.rank_hand(hole, board)).collect()
+    }
+
+    /// The five cards of `hole` and `board` which make the best hand, in the
+    /// order they are given. All cards are returned if there are fewer than
+    /// five, and the first combination wins when several rank the same.
+    pub fn best_hand(&self, hole: &[Card], board: &[Card]) -> Result<Vec<Card>, AcpcError> {
+	self.check_cards(hole.iter().chain(board))?;
+	let cards = [hole, board].concat();
+	if cards.len() <= 5 {
+	    return Ok(cards);
+	}
+	let mut best = (0, vec![]);
+	let mut idx = [0, 1, 2, 3, 4];
+	loop {
+	    let hand = idx.iter().map(|&i| cards[i]).collect::<Vec<_>>();
+	    let rank = unsafe {
+		acpc::rankCards(hand.as_ptr(), hand.len() as u8)
+	    };
+	    if best.1.is_empty() || rank > best.0 {
+		best = (rank, hand);
+	    }
+	    // Advance to the next combination in lexicographic order.
+	    let Some(i) = (0..5).rev().find(|&i| idx[i] < cards.len() - 5 + i) else {
+		break;
+	    };
+	    idx[i] += 1;
+	    for j in i + 1..5 {
+		idx[j] = idx[j - 1] + 1;
+	    }
+	}
+	Ok(best.1)
+    }
+
+    /// Render a card of this game like `"Qs"`.
+    ///
+    /// `NOT_DEALT` and cards which are not in the deck of this game are
+    /// rendered as `"??"`.
+    pub fn card_to_string(&self, card: Card) -> String {
+	if self.in_deck(card) {
+	    card_to_string(card)
+	} else {
+	    "??".to_owned()
+	}
+    }
+}
+
+impl PartialEq for Game {
+    fn eq(&self, other: &Game) -> bool {
+	let (a, b) = (&self.game_, &other.game_);
+	if a.bettingType != b.bettingType
+	    || a.numPlayers != b.numPlayers
+	    || a.numRounds != b.numRounds
+	    || a.numSuits != b.numSuits
+	    || a.numRanks != b.numRanks
+	    || a.numHoleCards != b.numHoleCards {
+	    return false;
+	}
+	let (p, r) = (a.numPlayers as usize, a.numRounds as usize);
+	a.stack[..p] == b.stack[..p]
+	    && a.blind[..p] == b.blind[..p]
+	    && a.firstPlayer[..r] == b.firstPlayer[..r]
+	    && a.maxRaises[..r] == b.maxRaises[..r]
+	    && a.numBoardCards[..r] == b.numBoardCards[..r]
+	    && (a.bettingType != acpc::BettingType_limitBetting
+		|| a.raiseSize[..r] == b.raiseSize[..r])
+    }
+}
+
+impl Eq for Game {}
+
+impl fmt::Display for Game {
+    /// Summary of the game such as
+    /// `"nolimit, 2 players, 4 rounds\nblinds 100 50\nstacks 20000 20000"`,
+    /// with a line of raise sizes in limit games.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	let join = |values: &[i32]| values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" ");
+	let betting_type = match self.betting_type() {
+	    BettingType::Limit => "limit",
+	    BettingType::NoLimit => "nolimit",
+	};
+	writeln!(f, "{}, {} players, {} rounds", betting_type, self.number_of_players(), self.number_of_rounds())?;
+	writeln!(f, "blinds {}", join(self.blinds()))?;
+	write!(f, "stacks {}", join(self.stacks()))?;
+	if self.betting_type() == BettingType::Limit {
+	    let raise_sizes = &self.game_.raiseSize[..self.number_of_rounds() as usize];
+	    write!(f, "\nraise sizes {}", join(raise_sizes))?;
+	}
+	Ok(())
+    }
+}
+
+/// Read a game with `readGame` from `c_file` and close it.
+///
+/// An `InvalidData` error is returned instead of the null pointer of
+/// `readGame` if the file is not a valid game definition, or if the game
+/// fails `Game::validate`.
+unsafe fn read_game(c_file: *mut libc::FILE) -> io::Result<acpc::Game> {
+    let game = acpc::readGame(c_file as *mut acpc::_IO_FILE);
+    libc::fclose(c_file);
+    if game.is_null() {
+	return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid game definition"));
+    }
+    let game_ = *game;
+    libc::free(game as *mut libc::c_void);
+    builder::validate(&game_).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(game_)
+}
+
+impl TryFrom<File> for Game {
+    type Error = io::Error;
+
+    /// Read a game definition like `Game::read`, returning an error instead
+    /// of failing if the file is not a valid game definition.
+    fn try_from(file: File) -> io::Result<Game> {
+	let game_ = unsafe {
+	    let fd = file.into_raw_fd();
+	    let c_file = libc::fdopen(
+		fd,
+		c"r".as_ptr(),
+	    );
+	    if c_file.is_null() {
+		let err = io::Error::last_os_error();
+		libc::close(fd);
+		return Err(err);
+	    }
+	    read_game(c_file)?
+	};
+	Ok(Game { hand_id: 0, game_ })
     }
 }
 
@@ -118,6 +761,19 @@ impl Game {
 pub struct State{
     pub game: Game,
     state_: acpc::State,
+    /// `spent` of the C state when each round started, recorded as the
+    /// rounds advance.
+    round_start_spent: RoundStarts,
+}
+
+type RoundStarts = [[i32; acpc::MAX_PLAYERS as usize]; acpc::MAX_ROUNDS as usize];
+
+/// Record `spent` of `state_` as the start of the rounds it advanced to
+/// since `prev_round`, including the rounds skipped when players are all in.
+fn record_round_starts(starts: &mut RoundStarts, prev_round: u8, state_: &acpc::State) {
+    for round in prev_round + 1..=state_.round {
+	starts[round as usize] = state_.spent;
+    }
 }
 
 impl State {
@@ -139,71 +795,446 @@ impl State {
     }
     
     pub fn new(game: Game) -> Self {
+	let hand_id = game.hand_id;
+	State::new_with_hand_id(game, hand_id)
+    }
+
+    /// Create the state at the start of the hand `hand_id`.
+    pub fn new_with_hand_id(game: Game, hand_id: u32) -> Self {
 	let mut state_ = State::new_acpc_state();
 	let state_ptr = &mut state_ as *mut acpc::State;
 	let game_ptr = &game.game_ as *const acpc::Game;
 	unsafe {
-	    acpc::initState(game_ptr, game.hand_id, state_ptr);
+	    acpc::initState(game_ptr, hand_id, state_ptr);
 	};
-	return State{ game, state_ };
+	let mut round_start_spent = [[0; acpc::MAX_PLAYERS as usize]; acpc::MAX_ROUNDS as usize];
+	round_start_spent[0] = state_.spent;
+	State { game, state_, round_start_spent }
+    }
+
+    /// Wrap a state filled in by the parsers of the C library, replaying
+    /// its actions once to record the spent at the start of each round.
+    fn from_acpc_state(game: Game, state_: acpc::State) -> Self {
+	let mut replayed = state_;
+	let state_ptr = &mut replayed as *mut acpc::State;
+	let game_ptr = &game.game_ as *const acpc::Game;
+	let mut round_start_spent = [[0; acpc::MAX_PLAYERS as usize]; acpc::MAX_ROUNDS as usize];
+	unsafe {
+	    acpc::initState(game_ptr, state_.handId, state_ptr);
+	    round_start_spent[0] = replayed.spent;
+	    for r in 0..=state_.round as usize {
+		for action in &state_.action[r][..state_.numActions[r] as usize] {
+		    let prev_round = replayed.round;
+		    acpc::doAction(game_ptr, action as *const acpc::Action, state_ptr);
+		    record_round_starts(&mut round_start_spent, prev_round, &replayed);
+		}
+	    }
+	}
+	State { game, state_, round_start_spent }
+    }
+
+    #[inline]
+    pub fn hand_id(&self) -> u32 {
+	self.state_.handId
+    }
+
+    #[inline]
+    pub fn set_hand_id(&mut self, hand_id: u32) {
+	self.state_.handId = hand_id;
+    }
+
+    /// Start a new hand with `hand_id` in place, without cloning the game.
+    ///
+    /// The betting history is cleared and all the cards are undealt, as in
+    /// a state returned by `new`.
+    pub fn reset(&mut self, hand_id: u32) {
+	self.state_ = State::new_acpc_state();
+	let state_ptr = &mut self.state_ as *mut acpc::State;
+	let game_ptr = &self.game.game_ as *const acpc::Game;
+	unsafe {
+	    acpc::initState(game_ptr, hand_id, state_ptr);
+	}
+	self.round_start_spent[0] = self.state_.spent;
+    }
+
+    /// Parse a MATCHSTATE line sent by the dealer, such as
+    /// `MATCHSTATE:0:10:crrc/:|AsKh/7h8d9c`.
+    ///
+    /// Returns the hand id, the position of the viewing player and the
+    /// state with the betting history and the visible cards.
+    pub fn from_match_state(game: &Game, line: &str) -> Result<(u32, u8, State), ParseError> {
+	let line = line.trim_end();
+	let err = || ParseError::InvalidMatchState(line.to_owned());
+	let c_line = CString::new(line).map_err(|_| err())?;
+	let mut match_state = acpc::MatchState {
+	    state: State::new_acpc_state(),
+	    viewingPlayer: 0,
+	};
+	let game_ptr = &game.game_ as *const acpc::Game;
+	let match_state_ptr = &mut match_state as *mut acpc::MatchState;
+	let result = unsafe {
+	    acpc::readMatchState(c_line.as_ptr(), game_ptr, match_state_ptr)
+	};
+	if result < 0 || result as usize != line.len() {
+	    return Err(err());
+	}
+	let state = State::from_acpc_state(game.clone(), match_state.state);
+	Ok((match_state.state.handId, match_state.viewingPlayer, state))
+    }
+
+    /// Render the state as a MATCHSTATE line seen by `viewer`, as the
+    /// dealer sends it.
+    ///
+    /// Hole cards of the other players are only shown after a showdown.
+    /// All cards which appear in the line must have been dealt.
+    pub fn to_match_state(&self, viewer: u8, hand_id: u32) -> Result<String, AcpcError> {
+	let viewer_idx = self.game.player_idx(viewer)?;
+	let num_hole_cards = self.game.num_hole_cards() as usize;
+	let num_board_cards = self.game.sum_board_cards(self.get_round()) as usize;
+	let num_players = self.game.number_of_players() as usize;
+	let showdown = self.is_finished()
+	    && self.num_folded() + 1 < self.game.number_of_players();
+	let visible_holes = (0..num_players)
+	    .filter(|&p| p == viewer_idx || (showdown && self.state_.playerFolded[p] == 0))
+	    .flat_map(|p| self.state_.holeCards[p][..num_hole_cards].iter());
+	if visible_holes.chain(self.state_.boardCards[..num_board_cards].iter())
+	    .any(|&c| c == NOT_DEALT) {
+	    return Err(AcpcError::CardsNotDealt);
+	}
+	let mut match_state = acpc::MatchState {
+	    state: self.state_,
+	    viewingPlayer: viewer_idx as u8,
+	};
+	match_state.state.handId = hand_id;
+	let game_ptr = &self.game.game_ as *const acpc::Game;
+	let match_state_ptr = &match_state as *const acpc::MatchState;
+	print_to_string(|max_len, ptr| unsafe {
+	    acpc::printMatchState(game_ptr, match_state_ptr, max_len, ptr)
+	}).ok_or(AcpcError::PrintFailed)
+    }
+
+    /// Render a finished hand as a `STATE` line of the dealer's log, the
+    /// `printState` line followed by the values of the players, e.g.
+    /// `STATE:3:r200ff:AsKh|2c3d|QcQd:-50|-100|150`.
+    ///
+    /// The hole cards of all players must have been dealt, as well as the
+    /// board cards up to the last round.
+    pub fn to_log_line(&self, hand_id: u32) -> Result<String, AcpcError> {
+	let values = self.values()?;
+	let num_hole_cards = self.game.num_hole_cards() as usize;
+	let num_board_cards = self.game.sum_board_cards(self.get_round()) as usize;
+	let num_players = self.game.number_of_players() as usize;
+	if self.state_.holeCards[..num_players].iter()
+	    .flat_map(|cards| cards[..num_hole_cards].iter())
+	    .chain(self.state_.boardCards[..num_board_cards].iter())
+	    .any(|&c| c == NOT_DEALT) {
+	    return Err(AcpcError::CardsNotDealt);
+	}
+	let mut state_ = self.state_;
+	state_.handId = hand_id;
+	let state_ptr = &state_ as *const acpc::State;
+	let game_ptr = &self.game.game_ as *const acpc::Game;
+	let line = print_to_string(|max_len, ptr| unsafe {
+	    acpc::printState(game_ptr, state_ptr, max_len, ptr)
+	}).ok_or(AcpcError::PrintFailed)?;
+	let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+	Ok(format!("{}:{}", line, values.join("|")))
+    }
+
+    /// Parse a `STATE` line of a dealer log such as `to_log_line` writes,
+    /// into the finished hand with all the cards set.
+    ///
+    /// The values of the players after the cards are required, and may be
+    /// followed by the names of the players as the dealer writes them.
+    /// They are only checked to be numbers, the values of the returned
+    /// state are computed from the cards.
+    pub fn from_log_line(game: &Game, line: &str) -> Result<State, ParseError> {
+	let line = line.trim_end();
+	let err = || ParseError::InvalidLogLine(line.to_owned());
+	let c_line = CString::new(line).map_err(|_| err())?;
+	let mut state_ = State::new_acpc_state();
+	let game_ptr = &game.game_ as *const acpc::Game;
+	let state_ptr = &mut state_ as *mut acpc::State;
+	let result = unsafe {
+	    acpc::readState(c_line.as_ptr(), game_ptr, state_ptr)
+	};
+	if result < 0 {
+	    return Err(err());
+	}
+	let mut rest = line[result as usize..].split(':');
+	let values = match (rest.next(), rest.next(), rest.next(), rest.next()) {
+	    (Some(""), Some(values), _, None) => values,
+	    _ => return Err(err()),
+	};
+	let values = values.split('|').map(|v| v.parse::<f64>()).collect::<Vec<_>>();
+	if values.len() != game.number_of_players() as usize || values.iter().any(|v| v.is_err()) {
+	    return Err(err());
+	}
+	let state = State::from_acpc_state(game.clone(), state_);
+	if !state.is_finished() {
+	    return Err(err());
+	}
+	Ok(state)
     }
 
     #[inline]
-    pub fn spent_of(&self, player: u8) -> Result<i32, String> {
+    pub fn spent_of(&self, player: u8) -> Result<i32, AcpcError> {
 	Ok(self.state_.spent[self.game.player_idx(player)?])
     }
 
     #[inline]
-    pub fn player_folded(&self, player: u8) -> Result<bool, String> {
+    pub fn player_folded(&self, player: u8) -> Result<bool, AcpcError> {
 	Ok(self.state_.playerFolded[self.game.player_idx(player)?] == 1)
     }
 
+    /// Whether `player` can still act, i.e. has neither folded nor gone
+    /// all-in.
+    pub fn is_active(&self, player: u8) -> Result<bool, AcpcError> {
+	Ok(!self.player_folded(player)? && !self.is_all_in(player)?)
+    }
+
+    /// Whether `player` has spent the whole stack, as counted by
+    /// `num_all_in`.
+    pub fn is_all_in(&self, player: u8) -> Result<bool, AcpcError> {
+	let p = self.game.player_idx(player)?;
+	Ok(self.state_.spent[p] >= self.game.game_.stack[p])
+    }
+
     #[inline]
     pub fn spents(&self) -> &[i32] {
 	let n = self.game.number_of_players() as usize;
 	&self.state_.spent[..n]
     }
 
+    /// What each player had spent when `round` started, so that e.g.
+    /// `spents()[p] - spent_at_round_start(r)[p]` is what `p` put in since
+    /// the start of `r`.
+    ///
+    /// The spents are recorded by `do_action` as the rounds advance. Rounds
+    /// skipped because players are all in started with the last action,
+    /// and rounds which have not started yet are an `InvalidRound`.
+    pub fn spent_at_round_start(&self, round: u8) -> Result<Vec<i32>, AcpcError> {
+	if round > self.get_round() {
+	    return Err(AcpcError::InvalidRound(round));
+	}
+	let n = self.game.number_of_players() as usize;
+	Ok(self.round_start_spent[round as usize][..n].to_vec())
+    }
+
     #[inline]
     pub fn max_spend(&self) -> i32 {
 	self.state_.maxSpent
     }
 
+    /// The largest amount spent by a player, same as `max_spend`.
+    #[inline]
+    pub fn max_spent(&self) -> i32 {
+	self.max_spend()
+    }
+
+    /// Chips `player` needs to add to call, like `call_amount` for any
+    /// player.
+    pub fn to_call(&self, player: u8) -> Result<i32, AcpcError> {
+	Ok(self.max_spent() - self.spent_of(player)?)
+    }
+
     #[inline]
     pub fn total_spent(&self) -> i32 {
 	let n = self.game.number_of_players() as usize;
 	self.state_.spent.iter().take(n).fold(0, |sum, i| sum + *i)
     }
 
+    /// Chips committed across all players.
     #[inline]
-    pub fn current_player(&self) -> u8 {
-	let state_ptr = &self.state_ as *const acpc::State;
-	let game_ptr = &self.game.game_ as *const acpc::Game;
-	unsafe {
-	    acpc::currentPlayer(game_ptr, state_ptr)
-	}
+    pub fn pot_size(&self) -> i32 {
+	self.total_spent()
+    }
+
+    /// Chips in the pot left behind by the players who folded.
+    pub fn dead_money(&self) -> i32 {
+	self.spents().iter()
+	    .zip(self.state_.playerFolded.iter())
+	    .filter(|&(_, &folded)| folded != 0)
+	    .map(|(&spent, _)| spent)
+	    .sum()
+    }
+
+    /// The chips committed by each player who has not folded, as
+    /// `(player, spent)`.
+    pub fn live_contributions(&self) -> Vec<(u8, i32)> {
+	self.spents().iter()
+	    .enumerate()
+	    .filter(|&(p, _)| self.state_.playerFolded[p] == 0)
+	    .map(|(p, &spent)| (p as u8, spent))
+	    .collect()
+    }
+
+    /// All the seats in the order they act in the current round, starting
+    /// from `Game::first_player`. Folded and all-in players are included.
+    pub fn players_in_action_order(&self) -> Vec<u8> {
+	let num_players = self.game.number_of_players();
+	let first = self.game.game_.firstPlayer[self.get_round() as usize];
+	(0..num_players).map(|i| (first + i) % num_players).collect()
+    }
+
+    /// Players who still have to act before the round closes, starting
+    /// with the current player: the players who are neither folded nor all
+    /// in, and either have not matched `max_spent` or have not acted since
+    /// the last raise of the round.
+    pub fn players_to_act(&self) -> Vec<u8> {
+	if self.is_finished() {
+	    return vec![];
+	}
+	let round = self.get_round() as usize;
+	let num_actions = self.num_actions() as usize;
+	let actions = &self.state_.action[round][..num_actions];
+	let since = actions.iter()
+	    .rposition(|a| a.type_ == acpc::ActionType_a_raise)
+	    .unwrap_or(0);
+	let acted = &self.state_.actingPlayer[round][since..num_actions];
+	let num_players = self.game.number_of_players();
+	let current = self.current_player();
+	(0..num_players)
+	    .map(|i| (current + i) % num_players)
+	    .filter(|&p| {
+		let idx = p as usize;
+		self.state_.playerFolded[idx] == 0
+		    && !self.is_all_in(p).unwrap()
+		    && (!acted.contains(&p) || self.state_.spent[idx] < self.state_.maxSpent)
+	    })
+	    .collect()
+    }
+
+    #[inline]
+    pub fn current_player(&self) -> u8 {
+	let state_ptr = &self.state_ as *const acpc::State;
+	let game_ptr = &self.game.game_ as *const acpc::Game;
+	unsafe {
+	    acpc::currentPlayer(game_ptr, state_ptr)
+	}
     }
 
     #[inline]
     pub fn current_spent(&self) -> i32 {
 	self.state_.spent[self.current_player() as usize]
     }
+
+    /// Chips the current player needs to add to call.
+    #[inline]
+    pub fn call_amount(&self) -> i32 {
+	self.max_spend() - self.current_spent()
+    }
+
+    /// The pot odds of the current player, the share of the pot after a
+    /// call which the call costs: `call / (pot + call)`.
+    ///
+    /// `None` if the call is a check or the hand is finished, and the call
+    /// is limited to the stack of the player.
+    pub fn pot_odds(&self) -> Option<f64> {
+	if self.is_finished() {
+	    return None;
+	}
+	let player = self.current_player();
+	let stack = self.game.stack_size(player).unwrap();
+	let call = self.call_amount().min(stack - self.current_spent());
+	if call <= 0 {
+	    return None;
+	}
+	Some(call as f64 / (self.total_spent() + call) as f64)
+    }
+
+    /// Whether `Action::Call` is a check, i.e. calling costs nothing.
+    #[inline]
+    pub fn is_check(&self) -> bool {
+	self.call_amount() == 0
+    }
     
     #[inline]
-    pub fn value_of_state(&self, player: u8) -> Result<f64, String> {
-	if !self.is_finished() {
-	    return Err("Game is not finished".to_owned());
+    pub fn value_of_state(&self, player: u8) -> Result<f64, AcpcError> {
+	let player_idx = self.game.player_idx(player)?;
+	Ok(self.values()?[player_idx])
+    }
+
+    /// The value of `player` like `value_of_state`, in big blinds, the
+    /// largest of the blinds of the game.
+    pub fn value_in_bb(&self, player: u8) -> Result<f64, AcpcError> {
+	let big_blind = self.game.blinds().iter().copied().max().unwrap_or(0);
+	Ok(self.value_of_state(player)? / big_blind as f64)
+    }
+
+    /// Values of a finished hand for all players.
+    ///
+    /// Same as `valueOfState` in game.c, but each hand is ranked only once
+    /// instead of once per player.
+    pub fn values(&self) -> Result<Vec<f64>, AcpcError> {
+	self.ensure_finished()?;
+	let spent = self.spents();
+	let folded = (0..spent.len())
+	    .map(|p| self.state_.playerFolded[p] != 0)
+	    .collect::<Vec<_>>();
+	if self.num_folded() as usize + 1 == spent.len() {
+	    // everyone else folded, so the remaining player takes the pot
+	    let pot = self.total_spent() as f64;
+	    return Ok((0..spent.len())
+		      .map(|p| if folded[p] { -spent[p] as f64 } else { pot - spent[p] as f64 })
+		      .collect());
 	}
+
 	let state_ptr = &self.state_ as *const acpc::State;
 	let game_ptr = &self.game.game_ as *const acpc::Game;
-	let player_idx = self.game.player_idx(player)?;
-	unsafe {
-	    Ok(acpc::valueOfState(game_ptr, state_ptr, player_idx as u8))
+	let mut values = spent.iter().map(|&s| -s as f64).collect::<Vec<_>>();
+	// (player, rank, remaining spent) of the players in the pot, folded
+	// players have a negative rank so they lose to a real hand
+	let mut pot = (0..spent.len())
+	    .filter(|&p| spent[p] > 0)
+	    .map(|p| {
+		let rank = if folded[p] {
+		    -1
+		} else {
+		    unsafe { acpc::rankHand(game_ptr, state_ptr, p as u8) }
+		};
+		(p, rank, spent[p])
+	    })
+	    .collect::<Vec<_>>();
+	for p in 0..spent.len() {
+	    if !folded[p] {
+		values[p] = 0.0;
+	    }
+	}
+
+	// go through the side pots from the smallest one
+	while pot.iter().any(|&(p, _, _)| !folded[p]) {
+	    let size = pot.iter().map(|&(_, _, s)| s).min().unwrap();
+	    let win_rank = pot.iter().map(|&(_, r, _)| r).max().unwrap().max(0);
+	    let num_winners = pot.iter().filter(|&&(_, r, _)| r == win_rank).count();
+	    for &(p, rank, _) in pot.iter().filter(|&&(p, _, _)| !folded[p]) {
+		if rank == win_rank {
+		    values[p] += (size as f64 * (pot.len() - num_winners) as f64)
+			/ num_winners as f64;
+		} else {
+		    values[p] -= size as f64;
+		}
+	    }
+	    pot = pot.into_iter()
+		.map(|(p, r, s)| (p, r, s - size))
+		.filter(|&(_, _, s)| s > 0)
+		.collect();
 	}
+	Ok(values)
+    }
+
+    /// Players who win money in a finished hand, all of them if the pot is
+    /// split.
+    pub fn winners(&self) -> Result<Vec<u8>, AcpcError> {
+	Ok(self.values()?.iter()
+	   .enumerate()
+	   .filter(|&(_, &v)| v > 0.0)
+	   .map(|(p, _)| p as u8)
+	   .collect())
     }
 
     #[inline]
-    pub fn raise_size(&self) -> Result<(i32, i32), String> {
+    pub fn raise_size(&self) -> Result<(i32, i32), AcpcError> {
 	let mut min_size = 0;
 	let mut max_size = 0;
 	let state_ptr = &self.state_ as *const acpc::State;
@@ -214,12 +1245,102 @@ impl State {
 	    acpc::raiseIsValid(game_ptr, state_ptr, min_size_ptr, max_size_ptr)
 	};
 	match result {
-	    0 => Err("player Can not raise now.".to_owned()),
+	    0 => Err(AcpcError::CannotRaise),
 	    1 => Ok((min_size, max_size)),
 	    _ => panic!("Invalid result from acpc::isValidAction {}", result),
 	}
     }
 
+    /// Raises for a bet abstraction of no-limit games, raising to
+    /// `pot_size() * fraction` for each of `fractions`.
+    ///
+    /// The totals are clamped to `raise_size` and duplicates are removed, so
+    /// the result is empty when the player can not raise.
+    pub fn raise_amounts_for_fractions(&self, fractions: &[f64]) -> Vec<Action> {
+	let (min, max) = match self.raise_size() {
+	    Ok(range) => range,
+	    Err(_) => return vec![],
+	};
+	let pot = self.pot_size() as f64;
+	let mut actions = vec![];
+	for fraction in fractions {
+	    let total = ((pot * fraction).round() as i32).clamp(min, max);
+	    let action = Action::Raise(total);
+	    if !actions.contains(&action) {
+		actions.push(action);
+	    }
+	}
+	actions
+    }
+
+    /// A single raise to `pot_size() * fraction` like
+    /// `raise_amounts_for_fractions`, but the total is not clamped: it is an
+    /// `InvalidAction` if it is outside of `raise_size`.
+    pub fn pot_fraction_raise(&self, fraction: f64) -> Result<Action, AcpcError> {
+	let (min, max) = self.raise_size()?;
+	let total = (self.pot_size() as f64 * fraction).round() as i32;
+	let action = Action::Raise(total);
+	if total < min || total > max {
+	    return Err(AcpcError::InvalidAction(action));
+	}
+	Ok(action)
+    }
+
+    /// The range of valid raises like `raise_size`, or `None` if the
+    /// current player can not raise.
+    pub fn raise_range(&self) -> Option<(i32, i32)> {
+	self.raise_size().ok()
+    }
+
+    /// `minNoLimitRaiseTo` of the C state, the smallest total a no-limit
+    /// raise has to reach before the stack of the player is considered.
+    ///
+    /// Only meaningful in no-limit games.
+    pub fn min_no_limit_raise_to(&self) -> i32 {
+	self.state_.minNoLimitRaiseTo
+    }
+
+    /// The smallest valid raise, the first value of `raise_size`.
+    pub fn min_raise(&self) -> Result<i32, AcpcError> {
+	self.raise_size().map(|(min, _)| min)
+    }
+
+    /// The largest valid raise, the second value of `raise_size`.
+    pub fn max_raise(&self) -> Result<i32, AcpcError> {
+	self.raise_size().map(|(_, max)| max)
+    }
+
+    /// The valid actions of the current player.
+    ///
+    /// Limit games have a single raise of the size of the round. No-limit
+    /// games have a raise to the minimum and to the maximum of `raise_size`.
+    /// The state is finished if the result is empty.
+    pub fn legal_actions(&self) -> Vec<Action> {
+	let mut actions = vec![Action::Fold, Action::Call];
+	match self.game.betting_type() {
+	    BettingType::Limit => {
+		if let Ok(size) = self.game.raise_size_for_round(self.get_round()) {
+		    actions.push(Action::Raise(size));
+		}
+	    }
+	    BettingType::NoLimit => {
+		if let Ok((min, max)) = self.raise_size() {
+		    actions.push(Action::Raise(min));
+		    if max != min {
+			actions.push(Action::Raise(max));
+		    }
+		}
+	    }
+	}
+	actions.into_iter().filter(|a| self.is_valid_action(*a)).collect()
+    }
+
+    /// Which actions of the fixed action space `space` are valid now, for
+    /// masking a discrete action space.
+    pub fn action_mask(&self, space: &[Action]) -> Vec<bool> {
+	space.iter().map(|&a| self.is_valid_action(a)).collect()
+    }
+
     #[inline]
     pub fn num_folded(&self) -> u8 {
 	let state_ptr = &self.state_ as *const acpc::State;
@@ -229,20 +1350,144 @@ impl State {
 	}
     }
 
-    pub fn do_action(&mut self, action: Action) -> Result<(), &str>{
+    /// Number of players who have not folded and can still win the pot.
+    ///
+    /// Unlike `num_acting_player`, players who are all in are counted: they
+    /// take no more actions but are still in the hand.
+    pub fn num_in_hand(&self) -> u8 {
+	self.game.number_of_players() - self.num_folded()
+    }
+
+    pub fn do_action(&mut self, action: Action) -> Result<(), AcpcError> {
 	if !self.is_valid_action(action) {
-	    return Err("Invalid Action");
+	    return Err(AcpcError::InvalidAction(action));
 	}
 	let acpc_action = to_acpc_action(&action);
 	let state_ptr = &mut self.state_ as *mut acpc::State;
 	let game_ptr = &self.game.game_ as *const acpc::Game;
 	let action_ptr = &acpc_action as *const acpc::Action;
+	let prev_round = self.get_round();
 	unsafe {
 	    acpc::doAction(game_ptr, action_ptr, state_ptr)
 	}
+	record_round_starts(&mut self.round_start_spent, prev_round, &self.state_);
+	#[cfg(feature = "debug-invariants")]
+	self.check_invariants();
+	Ok(())
+    }
+
+    /// Panic if the money or the round of the state are inconsistent.
+    #[cfg(any(test, feature = "debug-invariants"))]
+    fn check_invariants(&self) {
+	let num_players = self.game.number_of_players() as usize;
+	let spent = &self.state_.spent[..num_players];
+	let stack = self.game.stacks();
+	assert!(self.total_spent() as i64 <= self.game.total_money(),
+		"total spent {} is more than the total money", self.total_spent());
+	for p in 0..num_players {
+	    assert!(spent[p] <= stack[p], "player {} spent {} of a stack of {}", p, spent[p], stack[p]);
+	}
+	assert_eq!(spent.iter().max().copied(), Some(self.state_.maxSpent), "maxSpent");
+	assert!(self.get_round() < self.game.number_of_rounds(), "round {}", self.get_round());
+    }
+
+    /// Play the hand to the end, taking the action chosen by `policy` for
+    /// the current player each time.
+    ///
+    /// Stops with `AcpcError::InvalidAction` if the policy returns an
+    /// action which is not valid.
+    pub fn play_out<F: FnMut(&State) -> Action>(&mut self, mut policy: F) -> Result<(), AcpcError> {
+	while !self.is_finished() {
+	    let action = policy(self);
+	    self.do_action(action)?;
+	}
 	Ok(())
     }
 
+    /// Play the hand to the end choosing uniformly among `legal_actions`,
+    /// then deal the missing cards and return the values of all players.
+    ///
+    /// This takes a `DealerRng` rather than a generic `rand::Rng`: the crate
+    /// does not depend on `rand`, and the cards are dealt by `deal_cards`,
+    /// which draws them like the dealer does. The same seed gives the same
+    /// rollout.
+    pub fn random_rollout(&mut self, rng: &mut DealerRng) -> Result<Vec<f64>, AcpcError> {
+	self.play_out(|state| {
+	    let actions = state.legal_actions();
+	    actions[rng.next_u32() as usize % actions.len()]
+	})?;
+	self.deal_cards(rng);
+	self.values()
+    }
+
+    /// Return the state after `action` without changing this state.
+    pub fn apply(&self, action: Action) -> Result<State, AcpcError> {
+	let mut next = self.clone();
+	next.do_action(action)?;
+	Ok(next)
+    }
+
+    /// Whether taking `action` would finish the hand, without changing this
+    /// state.
+    pub fn action_terminates(&self, action: Action) -> Result<bool, AcpcError> {
+	Ok(self.apply(action)?.is_finished())
+    }
+
+    /// Overwrite `dst` with a copy of this state, reusing it in loops
+    /// which would otherwise create a new state for each node.
+    ///
+    /// A state owns no heap memory, so this is a plain copy of the C
+    /// structs into `dst`.
+    pub fn clone_into(&self, dst: &mut State) {
+	dst.game.hand_id = self.game.hand_id;
+	dst.game.game_ = self.game.game_;
+	dst.state_ = self.state_;
+	dst.round_start_spent = self.round_start_spent;
+    }
+
+    /// Take back the last action and return it, or `None` at the start of
+    /// the hand.
+    ///
+    /// The state is rebuilt by replaying all the other actions from the
+    /// start of the hand, so undoing costs as much as the length of the
+    /// history but no snapshots are kept. The cards are not changed.
+    pub fn undo_action(&mut self) -> Option<Action> {
+	let mut actions = (0..=self.get_round())
+	    .flat_map(|r| {
+		let num_actions = self.state_.numActions[r as usize] as usize;
+		self.state_.action[r as usize][..num_actions].to_vec()
+	    })
+	    .collect::<Vec<_>>();
+	let last = actions.pop()?;
+	let state_ptr = &mut self.state_ as *mut acpc::State;
+	let game_ptr = &self.game.game_ as *const acpc::Game;
+	unsafe {
+	    acpc::initState(game_ptr, self.state_.handId, state_ptr);
+	    for action in &actions {
+		let prev_round = (*state_ptr).round;
+		acpc::doAction(game_ptr, action as *const acpc::Action, state_ptr);
+		record_round_starts(&mut self.round_start_spent, prev_round, &*state_ptr);
+	    }
+	}
+	Some(from_acpc_action(&last))
+    }
+
+    /// Call `f` with the state after each of `legal_actions` and the
+    /// action, undoing the action afterwards, to walk the tree below this
+    /// state without cloning it.
+    ///
+    /// `f` may recurse by calling `for_each_child` on the state it gets,
+    /// but must hand it back with the same actions as it got it, since the
+    /// action is taken back with `undo_action`.
+    pub fn for_each_child<F: FnMut(&mut State, Action)>(&mut self, mut f: F) {
+	for action in self.legal_actions() {
+	    self.do_action(action).unwrap();
+	    f(self, action);
+	    let undone = self.undo_action();
+	    debug_assert_eq!(Some(action), undone, "the state was changed by for_each_child");
+	}
+    }
+
     #[inline]
     pub fn is_valid_action(&self, action: Action) -> bool {
 	let mut acpc_action = to_acpc_action(&action);
@@ -293,6 +1538,148 @@ impl State {
 	self.state_.numActions[round as usize]
     }
 
+    /// Number of actions taken in `round`, which is 0 for later rounds.
+    pub fn num_actions_in_round(&self, round: u8) -> Result<u8, AcpcError> {
+	Ok(self.state_.numActions[self.game.round_idx(round)?])
+    }
+
+    /// The betting of `round` in the notation of the ACPC protocol, e.g.
+    /// `"r200cc"`, or `"rrc"` in limit games where raises have no size.
+    pub fn round_betting_string(&self, round: u8) -> Result<String, AcpcError> {
+	self.game.round_idx(round)?;
+	let limit = self.game.betting_type() == BettingType::Limit;
+	Ok(self.action_history()
+	   .filter(|&(r, _, _)| r == round)
+	   .map(|(_, _, action)| match action {
+	       Action::Raise(_) if limit => Action::Raise(0).to_acpc_token(),
+	       action => action.to_acpc_token(),
+	   })
+	   .collect())
+    }
+
+    /// Take the actions of `betting`, ACPC action tokens with `/` between
+    /// the rounds such as `"r200r1000cc/c"`, starting from the current
+    /// round. This is the inverse of `round_betting_string`.
+    ///
+    /// Invalid tokens are an `ActionToken` error, and `InvalidRound` is
+    /// returned with the round of `betting` if its rounds do not match the
+    /// rounds of the state. The state is unchanged when an error is
+    /// returned.
+    pub fn replay_betting(&mut self, betting: &str) -> Result<(), AcpcError> {
+	let mut state = self.clone();
+	let mut round = state.get_round();
+	let mut rest = betting;
+	while let Some(c) = rest.chars().next() {
+	    if c == '/' {
+		round += 1;
+		if state.get_round() < round {
+		    return Err(AcpcError::InvalidRound(round));
+		}
+		rest = &rest[1..];
+		continue;
+	    }
+	    if state.get_round() != round {
+		return Err(AcpcError::InvalidRound(round));
+	    }
+	    let len = rest[c.len_utf8()..].find(|c: char| !c.is_ascii_digit())
+		.map_or(rest.len(), |i| i + c.len_utf8());
+	    let action = Action::from_acpc_token(&rest[..len])?;
+	    state.do_action(action)?;
+	    rest = &rest[len..];
+	}
+	*self = state;
+	Ok(())
+    }
+
+    /// The player who took the action `action_index` of `round`.
+    pub fn acting_player(&self, round: u8, action_index: u8) -> Result<u8, AcpcError> {
+	let r = self.game.round_idx(round)?;
+	if action_index >= self.state_.numActions[r] {
+	    return Err(AcpcError::InvalidActionIndex(action_index));
+	}
+	Ok(self.state_.actingPlayer[r][action_index as usize])
+    }
+
+    /// The player who took the last action, `None` at the start of the hand.
+    pub fn last_actor(&self) -> Option<u8> {
+	self.action_history().last().map(|(_, player, _)| player)
+    }
+
+    /// Whether the last action closed a betting round and started the
+    /// next one, i.e. the board cards of the new round are to be dealt.
+    ///
+    /// Not set when the last action finished the hand.
+    pub fn round_just_advanced(&self) -> bool {
+	let round = self.get_round() as usize;
+	round > 0 && !self.is_finished() && self.state_.numActions[round] == 0
+    }
+
+    /// Iterate over the actions taken so far as `(round, player, action)`.
+    pub fn action_history(&self) -> impl Iterator<Item = (u8, u8, Action)> + '_ {
+	(0..=self.get_round()).flat_map(move |r| {
+	    let num_actions = self.state_.numActions[r as usize] as usize;
+	    (0..num_actions).map(move |i| {
+		(r,
+		 self.state_.actingPlayer[r as usize][i],
+		 from_acpc_action(&self.state_.action[r as usize][i]))
+	    })
+	})
+    }
+
+    /// Key of the information set of `player` for tables of solvers.
+    ///
+    /// The key encodes the round, the betting sequence, the board cards
+    /// visible in the current round and the sorted hole cards of `player`,
+    /// so it does not depend on the cards of the other players.
+    pub fn info_set_key(&self, player: u8) -> Result<Vec<u8>, AcpcError> {
+	let mut hole_cards = self.hole_cards(player)?.to_vec();
+	hole_cards.sort_unstable();
+	let mut key = self.betting_key(player);
+	key.push(b':');
+	key.extend_from_slice(self.visible_board());
+	key.push(b'|');
+	key.extend_from_slice(&hole_cards);
+	Ok(key)
+    }
+
+    /// Key of the information set of `player` like `info_set_key`, with
+    /// the cards replaced by their bucket in the abstraction `abs`.
+    pub fn info_set_with<A: CardAbstraction>(&self, player: u8, abs: &A) -> Result<Vec<u8>, AcpcError> {
+	let bucket = abs.bucket(&self.game, self.hole_cards(player)?, self.visible_board());
+	let mut key = self.betting_key(player);
+	key.push(b'|');
+	key.extend_from_slice(&bucket.to_le_bytes());
+	Ok(key)
+    }
+
+    /// The player, the round and the betting sequence of an info set key.
+    fn betting_key(&self, player: u8) -> Vec<u8> {
+	let mut key = vec![player, self.get_round()];
+	let mut round = 0;
+	for (r, _, action) in self.action_history() {
+	    while round < r {
+		key.push(b'/');
+		round += 1;
+	    }
+	    match action {
+		Action::Fold => key.push(b'f'),
+		Action::Call => key.push(b'c'),
+		Action::Raise(size) => {
+		    key.push(b'r');
+		    key.extend_from_slice(&size.to_le_bytes());
+		}
+		Action::Invalid => key.push(b'?'),
+	    }
+	}
+	key
+    }
+
+    /// The board cards of the rounds so far.
+    fn visible_board(&self) -> &[Card] {
+	let num_board_cards = self.game.sum_board_cards(self.get_round()) as usize;
+	&self.state_.boardCards[..num_board_cards]
+    }
+
     #[inline]
     pub fn is_finished(&self) -> bool {
 	match self.state_.finished {
@@ -302,39 +1689,147 @@ impl State {
 	}	
     }
 
+    /// `GameNotFinished` unless the hand is finished, the guard of the
+    /// methods which need a finished hand such as `values`.
+    pub fn ensure_finished(&self) -> Result<(), AcpcError> {
+	if !self.is_finished() {
+	    return Err(AcpcError::GameNotFinished);
+	}
+	Ok(())
+    }
+
+    /// The state itself if the hand is finished, like `ensure_finished`.
+    pub fn require_finished(&self) -> Result<&Self, AcpcError> {
+	self.ensure_finished()?;
+	Ok(self)
+    }
+
+    /// How the hand ended, `None` if it is not finished.
+    pub fn terminal_kind(&self) -> Option<TerminalKind> {
+	if !self.is_finished() {
+	    return None;
+	}
+	let num_players = self.game.number_of_players();
+	if self.num_folded() + 1 < num_players {
+	    return Some(TerminalKind::Showdown);
+	}
+	(0..num_players)
+	    .find(|&p| self.state_.playerFolded[p as usize] == 0)
+	    .map(|winner| TerminalKind::FoldOut { winner })
+    }
+
     #[inline]
-    pub fn money(&self, player: u8) -> Result<i32, String> {
+    pub fn money(&self, player: u8) -> Result<i32, AcpcError> {
 	Ok(self.game.stack_size(player)? - self.spent_of(player)?)
     }
 
+    /// The chips `player` has left to bet, the stack minus what is spent.
+    /// Same as `money`.
+    pub fn stack_behind(&self, player: u8) -> Result<i32, AcpcError> {
+	self.money(player)
+    }
+
+    /// The smallest stack behind of the active players, which bounds what
+    /// can still be won or lost by betting. 0 if nobody is active.
+    pub fn effective_stack(&self) -> i32 {
+	(0..self.game.number_of_players())
+	    .filter(|&p| self.is_active(p).unwrap())
+	    .map(|p| self.stack_behind(p).unwrap())
+	    .min()
+	    .unwrap_or(0)
+    }
+
+    /// The largest the pot can become if the players who have not folded
+    /// bet all their stacks.
+    ///
+    /// A player can not put in more than the largest stack of the other
+    /// players in the hand can match, and the chips of the players who
+    /// folded stay in the pot, so this is `pot_size` once the hand is won
+    /// by a fold.
+    pub fn max_possible_pot(&self) -> i64 {
+	let num_players = self.game.number_of_players() as usize;
+	let in_hand = (0..num_players)
+	    .filter(|&p| self.state_.playerFolded[p] == 0)
+	    .collect::<Vec<_>>();
+	(0..num_players)
+	    .map(|p| {
+		let spent = self.state_.spent[p];
+		if !in_hand.contains(&p) {
+		    return spent as i64;
+		}
+		let matched = in_hand.iter()
+		    .filter(|&&q| q != p)
+		    .map(|&q| self.game.game_.stack[q])
+		    .max()
+		    .unwrap_or(spent);
+		self.game.game_.stack[p].min(matched).max(spent) as i64
+	    })
+	    .sum()
+    }
+
     #[inline]
-    pub fn ante(&self, player: u8) -> Result<i32, String> {
+    pub fn ante(&self, player: u8) -> Result<i32, AcpcError> {
 	Ok(self.spent_of(player)?)
     }
     
-    pub fn set_hole_cards(&mut self, player: u8, cards: &[Card]) -> Result<(), String> {
-	assert!(self.game.num_hole_cards() as usize == cards.len());
+    /// Set the hole cards of `player`.
+    ///
+    /// The cards must be in the deck of the game and must not be in the
+    /// hands of the other players or on the board.
+    pub fn set_hole_cards(&mut self, player: u8, cards: &[Card]) -> Result<(), AcpcError> {
+	let p = self.game.player_idx(player)?;
+	let num_hole_cards = self.game.num_hole_cards() as usize;
+	if cards.len() != num_hole_cards {
+	    return Err(AcpcError::InvalidCardCount(cards.len()));
+	}
+	self.game.check_cards(cards)?;
+	let num_players = self.game.number_of_players() as usize;
+	let mut in_use = (0..num_players)
+	    .filter(|&other| other != p)
+	    .flat_map(|other| self.state_.holeCards[other][..num_hole_cards].iter())
+	    .chain(self.state_.boardCards.iter());
+	if let Some(&card) = in_use.find(|c| cards.contains(c)) {
+	    return Err(AcpcError::DuplicateCard(card));
+	}
 	let mut fixed_size_cards: [Card; 3] = [0; 3];
-	for (i, v) in cards.into_iter().enumerate() {
+	for (i, v) in cards.iter().enumerate() {
 	    fixed_size_cards[i] = *v;
 	}
-	self.state_.holeCards[self.game.player_idx(player)?] = fixed_size_cards;
+	self.state_.holeCards[p] = fixed_size_cards;
 	Ok(())
     }
 
     #[inline]
-    pub fn hole_cards(&self, player: u8) -> Result<&[Card], String> {
+    pub fn hole_cards(&self, player: u8) -> Result<&[Card], AcpcError> {
 	let length = self.game.game_.numHoleCards as usize;
 	Ok(&self.state_.holeCards[self.game.player_idx(player)?][..length])
     }
 
-    pub fn set_board_cards(&mut self, cards: &[Card]) {
-	// assert!(self.game.sum_board_cards(self.get_round()) as usize == cards.len());
+    /// Set the board cards of all the rounds so far.
+    ///
+    /// The number of cards must be the total number of board cards of some
+    /// round, as returned by `sum_board_cards`. It may be that of a later
+    /// round, e.g. the whole board up to the river can be set before the
+    /// flop, in which case the cards are revealed as the rounds advance.
+    ///
+    /// The cards must be in the deck of the game and must not be in the
+    /// hands of the players.
+    pub fn set_board_cards(&mut self, cards: &[Card]) -> Result<(), AcpcError> {
+	if !self.game.rounds().any(|r| self.game.sum_board_cards(r) as usize == cards.len()) {
+	    return Err(AcpcError::InvalidCardCount(cards.len()));
+	}
+	let num_hole_cards = self.game.num_hole_cards() as usize;
+	let hole_cards = self.state_.holeCards[..self.game.number_of_players() as usize]
+	    .iter()
+	    .flat_map(|hand| hand[..num_hole_cards].iter())
+	    .filter(|&&card| card != NOT_DEALT);
+	self.game.check_cards(hole_cards.chain(cards))?;
 	let mut fixed_size_cards: [Card; 7] = [NOT_DEALT; 7];
 	for (i, v) in cards.into_iter().enumerate() {
 	    fixed_size_cards[i] = *v;
 	}
 	self.state_.boardCards = fixed_size_cards;
+	Ok(())
     }
 
     #[inline]
@@ -343,33 +1838,316 @@ impl State {
 	&self.state_.boardCards[..length]
     }
 
+    /// The board cards grouped by the round which reveals them, e.g. the
+    /// flop, the turn and the river of Hold'em.
+    ///
+    /// Rounds without board cards are skipped, and only the rounds whose
+    /// cards are all in `board_cards` are included.
+    pub fn board_by_round(&self) -> Vec<Vec<Card>> {
+	let board = self.board_cards();
+	let mut start = 0;
+	let mut groups = vec![];
+	for round in self.game.rounds() {
+	    let end = self.game.sum_board_cards(round) as usize;
+	    if end > board.len() {
+		break;
+	    }
+	    if end > start {
+		groups.push(board[start..end].to_vec());
+	    }
+	    start = end;
+	}
+	groups
+    }
+
     #[inline]
     pub fn get_round(&self) -> u8 {
 	self.state_.round
     }
 
-    pub fn deal_cards(&self) {
-	//
+    /// Whether cards are to be dealt before the next decision: some hole
+    /// cards or board cards of the current round are not dealt yet.
+    ///
+    /// Finished states are never chance nodes.
+    pub fn is_chance_node(&self) -> bool {
+	if self.is_finished() {
+	    return false;
+	}
+	let num_players = self.game.number_of_players() as usize;
+	let num_hole_cards = self.game.num_hole_cards() as usize;
+	let num_board_cards = self.game.sum_board_cards(self.get_round()) as usize;
+	self.state_.holeCards[..num_players].iter()
+	    .flat_map(|cards| cards[..num_hole_cards].iter())
+	    .chain(self.state_.boardCards[..num_board_cards].iter())
+	    .any(|&c| c == NOT_DEALT)
+    }
+
+    /// Deal hole cards and board cards up to the current round.
+    ///
+    /// Cards which are already set (by `set_hole_cards`, `set_board_cards`
+    /// or an earlier call) are kept and removed from the deck, so calling
+    /// this again after a round ends only deals the new board cards.
+    pub fn deal_cards(&mut self, rng: &mut DealerRng) {
+	let num_players = self.game.number_of_players() as usize;
+	let num_hole_cards = self.game.num_hole_cards() as usize;
+	let num_board_cards = self.game.sum_board_cards(self.get_round()) as usize;
+	let mut deck = self.remaining_deck();
+
+	for p in 0..num_players {
+	    for i in 0..num_hole_cards {
+		if self.state_.holeCards[p][i] == NOT_DEALT {
+		    self.state_.holeCards[p][i] = rng.deal_card(&mut deck);
+		}
+	    }
+	}
+	for i in 0..num_board_cards {
+	    if self.state_.boardCards[i] == NOT_DEALT {
+		self.state_.boardCards[i] = rng.deal_card(&mut deck);
+	    }
+	}
+    }
+
+    /// The cards of the deck which are neither on the board nor in the hand
+    /// of any player, in the order of `dealCard` in game.c.
+    pub fn remaining_deck(&self) -> Vec<Card> {
+	let num_players = self.game.number_of_players() as usize;
+	let num_hole_cards = self.game.num_hole_cards() as usize;
+
+	let dealt = self.state_.holeCards[..num_players].iter()
+	    .flat_map(|cards| cards[..num_hole_cards].iter())
+	    .chain(self.state_.boardCards.iter())
+	    .filter(|&c| *c != NOT_DEALT)
+	    .copied()
+	    .collect::<Vec<Card>>();
+	let mut deck = self.game.full_deck();
+	deck.retain(|card| !dealt.contains(card));
+	deck
+    }
+
+    /// Deal the cards like `deal_cards` with a generator seeded by `seed`,
+    /// so that the same seed always deals the same cards.
+    pub fn deal_cards_seeded(&mut self, seed: u64) {
+	self.deal_cards(&mut DealerRng::from_seed64(seed));
+    }
+
+    /// Estimate the share of the pot each player wins at the showdown by
+    /// dealing the missing hole cards and board cards up to the last round
+    /// `iterations` times.
+    ///
+    /// Cards which are already set are kept, and folded players have an
+    /// equity of 0. A tie splits the share between the winners.
+    pub fn estimate_equity(&self, iterations: usize, rng: &mut DealerRng) -> Result<Vec<f64>, AcpcError> {
+	let (holes, board) = self.showdown_cards();
+	let deck = self.remaining_deck();
+	let mut equity = vec![0.0; holes.len()];
+	for _ in 0..iterations {
+	    let mut deck = deck.clone();
+	    let shares = self.showdown_shares(&holes, &board, || rng.deal_card(&mut deck))?;
+	    for (e, share) in equity.iter_mut().zip(shares) {
+		*e += share;
+	    }
+	}
+	Ok(equity.iter().map(|e| e / iterations.max(1) as f64).collect())
+    }
+
+    /// The exact share of the pot each player wins at the showdown, going
+    /// through every way to deal the missing hole cards and board cards.
+    /// The order of the cards within a hand or on the board does not matter,
+    /// so each set of cards is dealt once.
+    ///
+    /// Meant for small games like Leduc and for late rounds, it fails with
+    /// `AcpcError::TooManyCompletions` above a million ways to deal.
+    pub fn exact_equity(&self) -> Result<Vec<f64>, AcpcError> {
+	let (holes, board) = self.showdown_cards();
+	let deck = self.remaining_deck();
+	// the number of missing cards of each hand and of the board, in the
+	// order `showdown_shares` fills them in
+	let groups = holes.iter().map(Vec::as_slice).chain([board.as_slice()])
+	    .map(|cards| cards.iter().filter(|&&c| c == NOT_DEALT).count())
+	    .collect::<Vec<_>>();
+	let mut left = deck.len();
+	let count = groups.iter().fold(1u64, |n, &k| {
+	    left -= k;
+	    n.saturating_mul(binomial(left + k, k))
+	});
+	if count > MAX_EXACT_COMPLETIONS {
+	    return Err(AcpcError::TooManyCompletions(count));
+	}
+	let mut equity = vec![0.0; holes.len()];
+	for_each_deal(&deck, &groups, &mut |dealt| {
+	    let mut next = dealt.iter().copied();
+	    let shares = self.showdown_shares(&holes, &board, || next.next().unwrap())?;
+	    for (e, share) in equity.iter_mut().zip(shares) {
+		*e += share;
+	    }
+	    Ok(())
+	})?;
+	Ok(equity.iter().map(|e| e / count as f64).collect())
+    }
+
+    /// The hole cards of the players who did not fold, empty for those who
+    /// did, and the board cards up to the last round, `NOT_DEALT` where
+    /// they are missing.
+    fn showdown_cards(&self) -> (Vec<Vec<Card>>, Vec<Card>) {
+	let num_players = self.game.number_of_players() as usize;
+	let num_hole_cards = self.game.num_hole_cards() as usize;
+	let last_round = self.game.number_of_rounds() - 1;
+	let num_board_cards = self.game.sum_board_cards(last_round) as usize;
+	let holes = (0..num_players)
+	    .map(|p| if self.state_.playerFolded[p] != 0 {
+		vec![]
+	    } else {
+		self.state_.holeCards[p][..num_hole_cards].to_vec()
+	    })
+	    .collect();
+	(holes, self.state_.boardCards[..num_board_cards].to_vec())
+    }
+
+    /// The share of the pot of each player at a showdown of `holes` and
+    /// `board`, where players with no hole cards have folded. The missing
+    /// cards are taken from `deal`.
+    fn showdown_shares<F: FnMut() -> Card>(&self, holes: &[Vec<Card>], board: &[Card], mut deal: F)
+					  -> Result<Vec<f64>, AcpcError> {
+	let mut fill = |c: &Card| if *c == NOT_DEALT { deal() } else { *c };
+	let holes = holes.iter()
+	    .map(|cards| cards.iter().map(&mut fill).collect::<Vec<_>>())
+	    .collect::<Vec<_>>();
+	let board = board.iter().map(&mut fill).collect::<Vec<_>>();
+	let ranks = holes.iter()
+	    .map(|hole| if hole.is_empty() {
+		Ok(None)
+	    } else {
+		self.game.rank_hand(hole, &board).map(Some)
+	    })
+	    .collect::<Result<Vec<_>, _>>()?;
+	let best = ranks.iter().max().copied().flatten();
+	let num_winners = ranks.iter().filter(|&&r| r.is_some() && r == best).count();
+	Ok(ranks.iter()
+	   .map(|&r| if r.is_some() && r == best { 1.0 / num_winners as f64 } else { 0.0 })
+	   .collect())
     }
 }
 
 
 impl fmt::Display for State {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-	// https://docs.rs/rustc-std-workspace-std/1.0.1/std/ffi/struct.CString.html#examples-3
-	let string = CString::new(" ".repeat(4096)).expect("CString::new failed");
-	let ptr = string.into_raw();
 	let state_ptr = &self.state_ as *const acpc::State;
 	let game_ptr = &self.game.game_ as *const acpc::Game;
-	let c_string = unsafe {
-	    let _ = acpc::printState(game_ptr, state_ptr, 4096, ptr);
-	    CString::from_raw(ptr)
-	};
-        write!(f, "{:?}", &c_string)
+	let string = print_to_string(|max_len, ptr| unsafe {
+	    acpc::printState(game_ptr, state_ptr, max_len, ptr)
+	}).ok_or(fmt::Error)?;
+	write!(f, "{}", string)
+    }
+}
+
+
+/// The number of ways to choose `k` of `n` items.
+fn binomial(n: usize, k: usize) -> u64 {
+    (0..k).fold(1u64, |c, i| c * (n - i) as u64 / (i as u64 + 1))
+}
+
+/// Call `f` with every way to deal distinct cards of `deck` to `groups`,
+/// `groups[i]` being the number of cards of group `i`. The cards of a group
+/// are chosen regardless of their order and `f` gets them group by group.
+fn for_each_deal<F>(deck: &[Card], groups: &[usize], f: &mut F) -> Result<(), AcpcError>
+where F: FnMut(&[Card]) -> Result<(), AcpcError> {
+    // `left` cards of the current group are still to be chosen, from
+    // `deck[start..]`, and `groups` are the groups after it
+    fn go<F>(deck: &[Card], used: &mut [bool], dealt: &mut Vec<Card>, groups: &[usize],
+	     left: usize, start: usize, f: &mut F) -> Result<(), AcpcError>
+    where F: FnMut(&[Card]) -> Result<(), AcpcError> {
+	if left == 0 {
+	    return match groups.split_first() {
+		Some((&k, rest)) => go(deck, used, dealt, rest, k, 0, f),
+		None => f(dealt),
+	    };
+	}
+	for i in start..deck.len() {
+	    if !used[i] {
+		used[i] = true;
+		dealt.push(deck[i]);
+		go(deck, used, dealt, groups, left - 1, i + 1, f)?;
+		dealt.pop();
+		used[i] = false;
+	    }
+	}
+	Ok(())
     }
+    go(deck, &mut vec![false; deck.len()], &mut vec![], groups, 0, 0, f)
+}
+
+
+/// Parse the MATCHSTATE lines of a log such as the dealer sends them, with
+/// the same items as `State::from_match_state`.
+///
+/// Lines which do not start with `MATCHSTATE:`, like comments and blank
+/// lines, are skipped. The iteration stops if reading fails.
+pub fn read_match_states<'a, R: BufRead + 'a>(game: &'a Game, reader: R)
+    -> impl Iterator<Item = Result<(u32, u8, State), ParseError>> + 'a {
+    reader.lines()
+	.map_while(Result::ok)
+	.filter(|line| line.starts_with("MATCHSTATE:"))
+	.map(move |line| State::from_match_state(game, &line))
 }
 
 
+#[cfg(test)]
+mod action_tests {
+    use super::*;
+
+    #[test]
+    fn from_acpc_token() {
+	assert_eq!(Ok(Action::Fold), Action::from_acpc_token("f"));
+	assert_eq!(Ok(Action::Call), Action::from_acpc_token("c"));
+	assert_eq!(Ok(Action::Call), Action::from_acpc_token("k"));
+	assert_eq!(Ok(Action::Raise(0)), Action::from_acpc_token("r"));
+	assert_eq!(Ok(Action::Raise(200)), Action::from_acpc_token("r200"));
+	assert_eq!(Ok(Action::Raise(200)), Action::from_acpc_token("b200"));
+    }
+
+    #[test]
+    fn default() {
+	assert_eq!(Action::Fold, Action::default());
+	assert_eq!(Action::default(), Action::BASIC[0]);
+    }
+
+    #[test]
+    fn display() {
+	assert_eq!("fold", Action::Fold.to_string());
+	assert_eq!("call", Action::Call.to_string());
+	assert_eq!("raise", Action::Raise(0).to_string());
+	assert_eq!("raise to 200", Action::Raise(200).to_string());
+	assert_eq!("invalid", Action::Invalid.to_string());
+	assert_eq!("P1: raise to 200", format!("P{}: {}", 1, Action::raise_to(200)));
+    }
+
+    #[test]
+    fn to_acpc_token() {
+	assert_eq!("f", Action::Fold.to_acpc_token());
+	assert_eq!("c", Action::Call.to_acpc_token());
+	assert_eq!("r", Action::Raise(0).to_acpc_token());
+	assert_eq!("r200", Action::Raise(200).to_acpc_token());
+	assert_eq!("", Action::Invalid.to_acpc_token());
+	for action in [Action::Fold, Action::Call, Action::Raise(0), Action::Raise(20000)] {
+	    assert_eq!(Ok(action), Action::from_acpc_token(&action.to_acpc_token()));
+	}
+    }
+
+    #[test]
+    fn from_acpc_token_invalid() {
+	assert_eq!(Err(ActionError::Empty), Action::from_acpc_token(""));
+	assert_eq!(Err(ActionError::UnknownAction('x')), Action::from_acpc_token("x"));
+	assert_eq!(Err(ActionError::InvalidSize("-5".to_owned())),
+		   Action::from_acpc_token("r-5"));
+	assert_eq!(Err(ActionError::InvalidSize("+5".to_owned())),
+		   Action::from_acpc_token("r+5"));
+	assert_eq!(Err(ActionError::InvalidSize("99999999999".to_owned())),
+		   Action::from_acpc_token("r99999999999"));
+	assert_eq!(Err(ActionError::InvalidToken("c200".to_owned())),
+		   Action::from_acpc_token("c200"));
+    }
+}
+
 #[cfg(test)]
 mod game_tests {
     use super::*;
@@ -386,60 +2164,383 @@ mod game_tests {
     }
 
     #[test]
-    fn bc_start() {
-	let game = get_game();
-	assert_eq!(0, game.bc_start(0));
-	assert_eq!(0, game.bc_start(1));
-    }    
-    
+    fn read_from() {
+	let bytes: &[u8] = include_bytes!("../resources/leduc.limit.2p.game");
+	assert_eq!(get_game(), Game::read_from(bytes).unwrap());
+	let file = File::open("resources/holdem.nolimit.3p.game").unwrap();
+	assert_eq!(get_game_nolimit(), Game::read_from(file).unwrap());
+	let err = Game::read_from(&b"numPlayers = 2\n"[..]).unwrap_err();
+	assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
     #[test]
-    fn sum_board_cards() {
-	let game = get_game();
-	assert_eq!(0, game.sum_board_cards(0));
-	assert_eq!(1, game.sum_board_cards(1));
+    fn try_from_file() {
+	let file = File::open("resources/leduc.limit.2p.game").unwrap();
+	assert_eq!(get_game(), Game::try_from(file).unwrap());
+	let err = Game::try_from(File::open("Cargo.toml").unwrap()).unwrap_err();
+	assert_eq!(io::ErrorKind::InvalidData, err.kind());
+	assert!(Game::try_from(File::open("/dev/null").unwrap()).is_err());
     }
 
     #[test]
-    fn stack_size() {
-	let game = get_game();
-	assert_eq!(game.stack_size(0), Ok(i32::MAX));
-	assert_eq!(game.stack_size(1), Ok(i32::MAX));
-	assert!(game.stack_size(2).is_err());
+    fn validate() {
+	assert_eq!(Ok(()), get_game().validate());
+	let mut game = get_game_nolimit();
+	game.game_.blind[1] = -100;
+	assert_eq!(Err(AcpcError::InvalidGame("negative blind of player 1".to_owned())), game.validate());
+
+	let path = std::env::temp_dir()
+	    .join(format!("acpc_server_validate_{}.game", std::process::id()));
+	let text = std::fs::read_to_string("resources/holdem.nolimit.3p.game").unwrap();
+	std::fs::write(&path, text.replace("blind = 50 100 0", "blind = 50 100 -10")).unwrap();
+	let err = Game::try_from(File::open(&path).unwrap()).unwrap_err();
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(io::ErrorKind::InvalidData, err.kind());
+	assert_eq!("Invalid game, negative blind of player 2", err.to_string());
+    }
 
-	let game = get_game_nolimit();
-	assert_eq!(game.stack_size(0), Ok(20000));
-	assert_eq!(game.stack_size(1), Ok(20000));
-	assert_eq!(game.stack_size(2), Ok(20000));
-	assert!(game.stack_size(3).is_err());
+    #[test]
+    #[should_panic(expected = "Failed to read the game: invalid game definition")]
+    fn read_invalid() {
+	Game::read(File::open("Cargo.toml").unwrap());
     }
-    
+
     #[test]
-    fn blind_size() {
-	let game = get_game();
-	assert_eq!(game.blind_size(0), Ok(1));
-	assert_eq!(game.blind_size(1), Ok(1));
-	assert!(game.stack_size(2).is_err());
+    #[should_panic(expected = "Failed to read the game")]
+    fn read_empty() {
+	Game::read(File::open("/dev/null").unwrap());
     }
 
     #[test]
-    fn total_money() {
-	let game = get_game();
-	assert_eq!(game.total_money(), (i32::MAX as i64) * 2);
+    fn read_dir() {
+	let games = Game::read_dir(Path::new("resources")).unwrap();
+	let names = games.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+	assert_eq!(vec!["holdem.limit.2p.reverse_blinds.game", "holdem.limit.3p.game",
+			"holdem.nolimit.2p.reverse_blinds.game", "holdem.nolimit.3p.game",
+			"holdem.nolimit.6p.game", "kuhn.limit.2p.game", "kuhn.limit.3p.game",
+			"leduc.limit.2p.game"], names);
+	assert_eq!(get_game(), games[7].1);
+	assert_eq!(get_game_nolimit(), games[3].1);
+	assert!(Game::read_dir(Path::new("resources/missing")).is_err());
     }
-}
 
-#[cfg(test)]
-mod state_tests {
-    use super::*;
-    use std::fs::File;
+    #[test]
+    fn standard_games() {
+	let games = Game::read_dir(Path::new("resources")).unwrap();
+	let cases = [
+	    (Game::leduc(), 2, BettingType::Limit, 7),
+	    (Game::holdem_limit_2p(), 2, BettingType::Limit, 0),
+	    (Game::holdem_nolimit_2p(), 2, BettingType::NoLimit, 2),
+	    (Game::holdem_nolimit_3p(), 3, BettingType::NoLimit, 3),
+	];
+	for (game, num_players, betting_type, idx) in cases {
+	    assert_eq!(num_players, game.number_of_players());
+	    assert_eq!(betting_type, game.betting_type());
+	    assert_eq!(games[idx].1, game);
+	}
+    }
 
-    fn get_state() -> State {
-	let file = File::open("resources/holdem.nolimit.3p.game").unwrap();
-	let game = Game::read(file);
-	State::new(game)
+    #[test]
+    fn betting_type() {
+	assert_eq!(BettingType::Limit, get_game().betting_type());
+	assert_eq!(BettingType::NoLimit, get_game_nolimit().betting_type());
     }
 
-    fn play_until_showdown(state: &mut State) {
+    #[test]
+    fn number_of_rounds() {
+	let game = get_game();
+	assert_eq!(2, game.number_of_rounds());
+	assert_eq!(vec![0, 1], game.rounds().collect::<Vec<_>>());
+	let game = get_game_nolimit();
+	assert_eq!(4, game.number_of_rounds());
+	assert_eq!(vec![0, 1, 2, 3], game.rounds().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn print() {
+	let path = std::env::temp_dir()
+	    .join(format!("acpc_server_print_{}.game", std::process::id()));
+	get_game_nolimit().print(File::create(&path).unwrap());
+	let text = std::fs::read_to_string(&path).unwrap();
+	assert!(text.starts_with("GAMEDEF\nnolimit\nnumPlayers = 3\n"));
+	assert!(text.ends_with("END GAMEDEF\n"));
+
+	let game = Game::read(File::open(&path).unwrap());
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(3, game.number_of_players());
+	assert_eq!(Ok(20000), game.stack_size(2));
+	assert_eq!(Ok(100), game.blind_size(1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Failed to print the game")]
+    fn print_read_only() {
+	get_game_nolimit().print(File::open("/dev/null").unwrap());
+    }
+
+    #[test]
+    fn write() {
+	let path = std::env::temp_dir()
+	    .join(format!("acpc_server_write_{}.game", std::process::id()));
+	let game = get_game_nolimit();
+	game.write(File::create(&path).unwrap()).unwrap();
+	let restored = Game::read(File::open(&path).unwrap());
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(game, restored);
+    }
+
+    #[test]
+    fn display() {
+	assert_eq!("nolimit, 3 players, 4 rounds\nblinds 50 100 0\nstacks 20000 20000 20000",
+		   get_game_nolimit().to_string());
+	let leduc = get_game().to_string();
+	assert!(leduc.starts_with("limit, 2 players, 2 rounds\n"));
+	assert!(leduc.ends_with("\nraise sizes 2 4"));
+    }
+
+    #[test]
+    fn eq() {
+	assert_eq!(get_game(), get_game());
+	assert_eq!(get_game_nolimit(), get_game_nolimit());
+	assert_ne!(get_game(), get_game_nolimit());
+	let file = File::open("resources/holdem.nolimit.2p.reverse_blinds.game").unwrap();
+	assert_ne!(get_game_nolimit(), Game::read(file));
+	let file = File::open("resources/holdem.limit.3p.game").unwrap();
+	assert_ne!(get_game_nolimit(), Game::read(file));
+    }
+
+    #[test]
+    fn write_read_only() {
+	let file = File::open("resources/holdem.nolimit.3p.game").unwrap();
+	assert!(get_game_nolimit().write(file).is_err());
+    }
+
+    #[test]
+    fn bc_start() {
+	let game = get_game();
+	assert_eq!(Ok(0), game.bc_start(0));
+	assert_eq!(Ok(0), game.bc_start(1));
+	assert_eq!(Err(AcpcError::InvalidRound(2)), game.bc_start(2));
+	let game = get_game_nolimit();
+	let starts = game.rounds().map(|r| game.bc_start(r).unwrap()).collect::<Vec<_>>();
+	assert_eq!(vec![0, 0, 3, 4], starts);
+	assert_eq!(Err(AcpcError::InvalidRound(4)), game.bc_start(game.number_of_rounds()));
+    }    
+    
+    #[test]
+    fn sum_board_cards() {
+	let game = get_game();
+	assert_eq!(0, game.sum_board_cards(0));
+	assert_eq!(1, game.sum_board_cards(1));
+    }
+
+    #[test]
+    fn board_cards_in_round() {
+	let game = get_game();
+	assert_eq!(Ok(0), game.board_cards_in_round(0));
+	assert_eq!(Ok(1), game.board_cards_in_round(1));
+	assert_eq!(Err(AcpcError::InvalidRound(2)), game.board_cards_in_round(2));
+	let game = get_game_nolimit();
+	let counts = game.rounds()
+	    .map(|r| game.board_cards_in_round(r).unwrap())
+	    .collect::<Vec<_>>();
+	assert_eq!(vec![0, 3, 1, 1], counts);
+	for r in 1..4 {
+	    assert_eq!(game.sum_board_cards(r) - game.sum_board_cards(r - 1),
+		       game.board_cards_in_round(r).unwrap());
+	}
+    }
+
+    #[test]
+    fn deck_geometry() {
+	let game = get_game();
+	assert_eq!(2, game.number_of_suits());
+	assert_eq!(3, game.number_of_ranks());
+	assert_eq!(1, game.num_hole_cards());
+	let game = get_game_nolimit();
+	assert_eq!(4, game.number_of_suits());
+	assert_eq!(13, game.number_of_ranks());
+	assert_eq!(2, game.num_hole_cards());
+    }
+
+    #[test]
+    fn stack_size() {
+	let game = get_game();
+	assert_eq!(game.stack_size(0), Ok(i32::MAX));
+	assert_eq!(game.stack_size(1), Ok(i32::MAX));
+	assert!(game.stack_size(2).is_err());
+
+	let game = get_game_nolimit();
+	assert_eq!(game.stack_size(0), Ok(20000));
+	assert_eq!(game.stack_size(1), Ok(20000));
+	assert_eq!(game.stack_size(2), Ok(20000));
+	assert!(game.stack_size(3).is_err());
+    }
+
+    #[test]
+    fn stacks_and_blinds() {
+	let game = get_game_nolimit();
+	assert_eq!(&[20000, 20000, 20000], game.stacks());
+	assert_eq!(&[50, 100, 0], game.blinds());
+	for p in 0..game.number_of_players() {
+	    assert_eq!(Ok(game.stacks()[p as usize]), game.stack_size(p));
+	    assert_eq!(Ok(game.blinds()[p as usize]), game.blind_size(p));
+	}
+	assert_eq!(2, get_game().stacks().len());
+    }
+    
+    #[test]
+    fn blind_size() {
+	let game = get_game();
+	assert_eq!(game.blind_size(0), Ok(1));
+	assert_eq!(game.blind_size(1), Ok(1));
+	assert!(game.stack_size(2).is_err());
+    }
+
+    #[test]
+    fn raise_size_for_round() {
+	let game = get_game();
+	assert_eq!(Ok(2), game.raise_size_for_round(0));
+	assert_eq!(Ok(4), game.raise_size_for_round(1));
+	assert!(game.raise_size_for_round(2).is_err());
+    }
+
+    #[test]
+    fn first_player() {
+	let game = get_game_nolimit();
+	assert_eq!(Ok(2), game.first_player(0));
+	assert_eq!(Ok(0), game.first_player(1));
+	assert_eq!(Ok(0), game.first_player(3));
+	assert!(game.first_player(4).is_err());
+
+	let file = File::open("resources/holdem.nolimit.2p.reverse_blinds.game").unwrap();
+	let game = Game::read(file);
+	assert_eq!(Ok(1), game.first_player(0));
+	assert_eq!(Ok(0), game.first_player(1));
+    }
+
+    #[test]
+    fn button_seat() {
+	let game = get_game_nolimit();
+	assert_eq!(Ok(1), game.button_seat(0));
+	assert_eq!(Ok(2), game.button_seat(1));
+	assert_eq!(Err(AcpcError::InvalidRound(4)), game.button_seat(4));
+
+	let file = File::open("resources/holdem.nolimit.2p.reverse_blinds.game").unwrap();
+	let game = Game::read(file);
+	assert_eq!(Ok(0), game.button_seat(0));
+	assert_eq!(Ok(1), game.button_seat(1));
+    }
+
+    #[test]
+    fn max_raises() {
+	let game = get_game();
+	assert_eq!(Ok(2), game.max_raises(0));
+	assert_eq!(Ok(2), game.max_raises(1));
+	assert!(game.max_raises(2).is_err());
+	assert_eq!(Ok(u8::MAX), get_game_nolimit().max_raises(0));
+
+	let mut state = State::new(game.clone());
+	for _ in 0..game.max_raises(0).unwrap() {
+	    assert!(state.is_valid_action(Action::Raise(0)));
+	    state.do_action(Action::Raise(0)).unwrap();
+	}
+	assert!(!state.is_valid_action(Action::Raise(0)));
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(1, state.get_round());
+	assert!(state.is_valid_action(Action::Raise(0)));
+    }
+
+    #[test]
+    fn total_money() {
+	let game = get_game();
+	assert_eq!(game.total_money(), (i32::MAX as i64) * 2);
+    }
+
+    #[test]
+    fn card_to_string() {
+	let game = get_game_nolimit();
+	for card in 0..52 {
+	    assert_eq!(Ok(card), parse_card(&game.card_to_string(card)));
+	}
+	assert_eq!("Qs", game.card_to_string(parse_card("Qs").unwrap()));
+	assert_eq!("??", game.card_to_string(NOT_DEALT));
+	assert_eq!("??", game.card_to_string(52));
+
+	let game = get_game();
+	assert_eq!("Qh", game.card_to_string(parse_card("Qh").unwrap()));
+	assert_eq!("As", game.card_to_string(parse_card("As").unwrap()));
+	assert_eq!("??", game.card_to_string(parse_card("Jh").unwrap()));
+	assert_eq!("??", game.card_to_string(parse_card("Ad").unwrap()));
+	assert_eq!("??", game.card_to_string(NOT_DEALT));
+    }
+
+    #[test]
+    fn rank_hand() {
+	let game = get_game_nolimit();
+	let board = parse_cards("Ts Jh Qd 2c 2s").unwrap();
+	let straight = game.rank_hand(&parse_cards("AhKc").unwrap(), &board).unwrap();
+	let pair = game.rank_hand(&parse_cards("7h8c").unwrap(), &board).unwrap();
+	let two_pair = game.rank_hand(&parse_cards("Qh8c").unwrap(), &board).unwrap();
+	assert!(straight > two_pair);
+	assert!(two_pair > pair);
+	assert_eq!(pair, game.rank_hand(&parse_cards("8c7h").unwrap(), &board).unwrap());
+
+	assert_eq!(Err(AcpcError::DuplicateCard(parse_card("Ts").unwrap())),
+		   game.rank_hand(&parse_cards("TsKc").unwrap(), &board));
+	assert_eq!(Err(AcpcError::InvalidCard(NOT_DEALT)), game.rank_hand(&[NOT_DEALT], &board));
+	let game = get_game();
+	assert_eq!(Err(AcpcError::InvalidCard(parse_card("Jh").unwrap())),
+		   game.rank_hand(&parse_cards("Jh").unwrap(), &[]));
+	let pair = game.rank_hand(&parse_cards("Qh").unwrap(), &parse_cards("Qs").unwrap());
+	let high = game.rank_hand(&parse_cards("Ah").unwrap(), &parse_cards("Qs").unwrap());
+	assert!(pair.unwrap() > high.unwrap());
+    }
+
+    #[test]
+    fn full_deck() {
+	let deck = get_game().full_deck();
+	assert_eq!(parse_cards("QhKhAhQsKsAs").unwrap(), deck);
+	let deck = get_game_nolimit().full_deck();
+	assert_eq!(52, deck.len());
+	assert_eq!(parse_cards("2c3c4c").unwrap(), deck[..3]);
+	for (i, &card) in deck.iter().enumerate() {
+	    assert!(get_game_nolimit().in_deck(card));
+	    assert_eq!(Ok(card), parse_card(&crate::card_to_string(card)));
+	    assert!(!deck[..i].contains(&card));
+	}
+    }
+
+    #[test]
+    fn best_hand() {
+	let game = get_game_nolimit();
+	let flush = game.best_hand(&parse_cards("AhKd").unwrap(),
+				   &parse_cards("9h Th Jh 2h Qc").unwrap());
+	assert_eq!(Ok(parse_cards("Ah 9h Th Jh 2h").unwrap()), flush);
+	let full_house = game.best_hand(&parse_cards("7s7d").unwrap(),
+					&parse_cards("7c Ks Kd 2h 2c").unwrap());
+	assert_eq!(Ok(parse_cards("7s 7d 7c Ks Kd").unwrap()), full_house);
+	let board_plays = game.best_hand(&parse_cards("2c3d").unwrap(),
+					 &parse_cards("As Ks Qs Js Ts").unwrap());
+	assert_eq!(Ok(parse_cards("As Ks Qs Js Ts").unwrap()), board_plays);
+	assert_eq!(Ok(parse_cards("2c 3d As").unwrap()),
+		   game.best_hand(&parse_cards("2c3d").unwrap(), &parse_cards("As").unwrap()));
+	assert!(game.best_hand(&parse_cards("2c2c").unwrap(), &[]).is_err());
+    }
+}
+
+#[cfg(test)]
+mod state_tests {
+    use super::*;
+    use std::fs::File;
+
+    fn get_state() -> State {
+	let file = File::open("resources/holdem.nolimit.3p.game").unwrap();
+	let game = Game::read(file);
+	State::new(game)
+    }
+
+    fn play_until_showdown(state: &mut State) {
 	loop {
 	    if state.is_finished() {
 		return 
@@ -465,6 +2566,18 @@ mod state_tests {
 	assert!(state.value_of_state(3).is_err());
     }
 
+    #[test]
+    fn errors() {
+	let mut state = get_state();
+	assert_eq!(Err(AcpcError::GameNotFinished), state.value_of_state(0));
+	assert_eq!(Err(AcpcError::InvalidPlayer(3)), state.spent_of(3));
+	assert_eq!(Err(AcpcError::InvalidAction(Action::Raise(100))),
+		   state.do_action(Action::Raise(100)));
+	state.do_action(Action::Raise(20000)).unwrap();
+	assert_eq!(Err(AcpcError::CannotRaise), state.raise_size());
+	assert_eq!(Err(AcpcError::InvalidRound(4)), state.game.first_player(4));
+    }
+
     #[test]
     fn raise_size() {
 	let mut state = get_state();
@@ -483,6 +2596,140 @@ mod state_tests {
 	assert_eq!(&[1000, 20000, 200], state.spents());
     }
 
+    #[test]
+    fn min_no_limit_raise_to() {
+	let mut state = get_state();
+	for action in [Action::Raise(200), Action::Raise(1000), Action::Call] {
+	    assert_eq!(state.min_raise(), Ok(state.min_no_limit_raise_to()));
+	    state.do_action(action).unwrap();
+	}
+	assert_eq!(Ok((1800, 20000)), state.raise_size());
+	assert_eq!(1800, state.min_no_limit_raise_to());
+    }
+
+    #[test]
+    fn raise_amounts_for_fractions() {
+	let mut state = get_state();
+	let fractions = [0.5, 1.0, 2.0];
+	assert_eq!(150, state.pot_size());
+	assert_eq!(vec![Action::Raise(200), Action::Raise(300)],
+		   state.raise_amounts_for_fractions(&fractions));
+	state.do_action(Action::Raise(1000)).unwrap();
+	assert_eq!(1150, state.pot_size());
+	assert_eq!(vec![Action::Raise(1900), Action::Raise(2300)],
+		   state.raise_amounts_for_fractions(&fractions));
+	assert_eq!(vec![Action::Raise(20000)], state.raise_amounts_for_fractions(&[100.0]));
+	state.do_action(Action::Raise(20000)).unwrap();
+	assert!(state.raise_amounts_for_fractions(&fractions).is_empty());
+    }
+
+    #[test]
+    fn pot_fraction_raise() {
+	let mut state = get_state();
+	// raising to half or all of the pot of 150 is less than the big blind
+	assert_eq!(Err(AcpcError::InvalidAction(Action::Raise(75))), state.pot_fraction_raise(0.5));
+	assert_eq!(Err(AcpcError::InvalidAction(Action::Raise(150))), state.pot_fraction_raise(1.0));
+	assert_eq!(Ok(Action::Raise(300)), state.pot_fraction_raise(2.0));
+	state.do_action(Action::Raise(1000)).unwrap();
+	assert_eq!(Ok(Action::Raise(2300)), state.pot_fraction_raise(2.0));
+	assert_eq!(Err(AcpcError::InvalidAction(Action::Raise(115000))), state.pot_fraction_raise(100.0));
+	state.do_action(Action::Raise(20000)).unwrap();
+	assert_eq!(Err(AcpcError::CannotRaise), state.pot_fraction_raise(1.0));
+    }
+
+    #[test]
+    fn call_amount() {
+	let mut state = get_state();
+	assert_eq!(100, state.call_amount());
+	assert!(!state.is_check());
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(50, state.call_amount());
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(0, state.call_amount());
+	assert!(state.is_check());
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(1, state.get_round());
+	assert!(state.is_check());
+	state.do_action(Action::Raise(300)).unwrap();
+	assert_eq!(200, state.call_amount());
+	assert!(!state.is_check());
+    }
+
+    #[test]
+    fn pot_odds() {
+	let mut state = get_state();
+	assert_eq!(Some(100.0 / 250.0), state.pot_odds());
+	state.do_action(Action::Raise(300)).unwrap();
+	// p0 pays 250 to call into a pot of 50 + 100 + 300
+	assert_eq!(Some(250.0 / (450.0 + 250.0)), state.pot_odds());
+	state.do_action(Action::Call).unwrap();
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(None, state.pot_odds());
+	state.do_action(Action::Raise(20000)).unwrap();
+	assert_eq!(Some(19700.0 / (20600.0 + 19700.0)), state.pot_odds());
+	state.do_action(Action::Fold).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	assert_eq!(None, state.pot_odds());
+    }
+
+    #[test]
+    fn to_call() {
+	let mut state = get_state();
+	assert_eq!(Ok(50), state.to_call(0));
+	assert_eq!(Ok(0), state.to_call(1));
+	assert_eq!(Ok(100), state.to_call(2));
+	state.do_action(Action::Raise(300)).unwrap();
+	assert_eq!(300, state.max_spent());
+	assert_eq!(state.max_spend(), state.max_spent());
+	assert_eq!(Ok(250), state.to_call(0));
+	assert_eq!(Ok(200), state.to_call(1));
+	assert_eq!(Ok(0), state.to_call(2));
+	assert_eq!(state.to_call(state.current_player()), Ok(state.call_amount()));
+	assert_eq!(Err(AcpcError::InvalidPlayer(3)), state.to_call(3));
+    }
+
+    #[test]
+    fn raise_range() {
+	let mut state = get_state();
+	assert_eq!(Some((200, 20000)), state.raise_range());
+	state.do_action(Action::Raise(20000)).unwrap();
+	assert_eq!(None, state.raise_range());
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(None, state.raise_range());
+    }
+
+    #[test]
+    fn min_and_max_raise() {
+	let mut state = get_state();
+	assert_eq!(Ok(200), state.min_raise());
+	assert_eq!(Ok(20000), state.max_raise());
+	state.do_action(Action::Raise(200)).unwrap();
+	assert_eq!(Ok(300), state.min_raise());
+	state.do_action(Action::Raise(1000)).unwrap();
+	assert_eq!(Ok(1800), state.min_raise());
+	assert_eq!(Ok(20000), state.max_raise());
+	state.do_action(Action::Raise(20000)).unwrap();
+	assert_eq!(Err(AcpcError::CannotRaise), state.min_raise());
+	assert_eq!(Err(AcpcError::CannotRaise), state.max_raise());
+    }
+
+    #[test]
+    fn raise_to_and_by() {
+	let mut state = get_state();
+	assert_eq!(Action::Raise(200), Action::raise_to(200));
+	assert_eq!(Action::Raise(200), Action::raise_by(&state, 100));
+	assert_eq!(Ok((200, 20000)), state.raise_size());
+	state.do_action(Action::raise_by(&state, 100)).unwrap();
+	assert_eq!(&[50, 100, 200], state.spents());
+
+	assert_eq!(Ok((300, 20000)), state.raise_size());
+	assert_eq!(Action::raise_to(1000), Action::raise_by(&state, 800));
+	state.do_action(Action::raise_by(&state, 800)).unwrap();
+	assert_eq!(&[1000, 100, 200], state.spents());
+	assert!(!state.is_valid_action(Action::raise_by(&state, 799)));
+	assert!(state.is_valid_action(Action::raise_by(&state, 800)));
+    }
+
     #[test]
     fn num_folded() {
 	let mut state = get_state();
@@ -493,6 +2740,17 @@ mod state_tests {
 	assert_eq!(2, state.num_acting_player());
     }
 
+    #[test]
+    fn num_in_hand() {
+	let mut state = get_state();
+	assert_eq!(3, state.num_in_hand());
+	state.do_action(Action::Raise(20000)).unwrap(); // p2 all in
+	state.do_action(Action::Fold).unwrap();
+	assert_eq!(2, state.num_in_hand());
+	assert_eq!(1, state.num_acting_player());
+	assert_eq!(1, state.num_all_in());
+    }
+
     #[test]
     fn current_player() {
 	let mut state = get_state();
@@ -539,6 +2797,17 @@ mod state_tests {
 	assert_eq!(false, state.is_valid_action(Action::Raise(20001)));
     }
 
+    #[test]
+    fn action_mask() {
+	let space = [Action::Fold, Action::Call, Action::Raise(200), Action::Raise(1000),
+		     Action::Raise(20000)];
+	let mut state = get_state();
+	assert_eq!(vec![true; 5], state.action_mask(&space));
+	state.do_action(Action::Raise(1000)).unwrap();
+	assert_eq!(vec![true, true, false, false, true], state.action_mask(&space));
+	assert!(state.action_mask(&[]).is_empty());
+    }
+
     #[test]
     fn money_and_ante() {
 	let mut state = get_state();
@@ -558,6 +2827,47 @@ mod state_tests {
 	assert_eq!(4000, state.total_spent());
     }
 
+    #[test]
+    fn effective_stack() {
+	let mut state = get_state();
+	assert_eq!(19900, state.effective_stack());
+	state.do_action(Action::Raise(200)).unwrap(); // 2
+	state.do_action(Action::Raise(1000)).unwrap(); // 0
+	assert_eq!(Ok(19000), state.stack_behind(0));
+	assert_eq!(state.money(2), state.stack_behind(2));
+	assert_eq!(19000, state.effective_stack());
+	state.do_action(Action::Fold).unwrap(); // 1
+	state.do_action(Action::Raise(20000)).unwrap(); // 2 all-in
+	assert_eq!(19000, state.effective_stack());
+	assert_eq!(Err(AcpcError::InvalidPlayer(3)), state.stack_behind(3));
+    }
+
+    #[test]
+    fn pot_size() {
+	let mut state = get_state();
+	assert_eq!(150, state.pot_size());
+	state.do_action(Action::Raise(1000)).unwrap();
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(2100, state.pot_size());
+	assert_eq!(state.total_spent(), state.pot_size());
+    }
+
+    #[test]
+    fn dead_money() {
+	let mut state = get_state();
+	assert_eq!(0, state.dead_money());
+	assert_eq!(vec![(0, 50), (1, 100), (2, 0)], state.live_contributions());
+	state.do_action(Action::Raise(200)).unwrap(); // 2
+	state.do_action(Action::Raise(1000)).unwrap(); // 0
+	state.do_action(Action::Fold).unwrap(); // 1
+	assert_eq!(100, state.dead_money());
+	assert_eq!(vec![(0, 1000), (2, 200)], state.live_contributions());
+	state.do_action(Action::Fold).unwrap(); // 2
+	assert_eq!(300, state.dead_money());
+	assert_eq!(vec![(0, 1000)], state.live_contributions());
+	assert_eq!(state.pot_size(), state.dead_money() + 1000);
+    }
+
     #[test]
     fn num_called() {
 	let mut state = get_state();
@@ -596,39 +2906,896 @@ mod state_tests {
 	assert_eq!(0, state.num_actions());
     }
 
+    fn c_values(state: &State) -> Vec<f64> {
+	let state_ptr = &state.state_ as *const acpc::State;
+	let game_ptr = &state.game.game_ as *const acpc::Game;
+	(0..3).map(|p| unsafe { acpc::valueOfState(game_ptr, state_ptr, p) })
+	    .collect()
+    }
+
     #[test]
-    fn board_card() {
+    fn values() {
 	let mut state = get_state();
-	assert_eq!(0, state.board_cards().len());
-	let board = [17, 19, 23];
-	state.set_board_cards(&board);
-	assert_eq!(&board[..], state.board_cards());
-	let board = [17, 19, 23, 24];
-	state.set_board_cards(&board);
-	assert_eq!(&board[..], state.board_cards());
-	let board = [17, 19, 23, 24, 25];
-	state.set_board_cards(&board);
-	assert_eq!(&board[..], state.board_cards());
+	assert_eq!(Err(AcpcError::GameNotFinished), state.values());
+	state.do_action(Action::Fold).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	assert_eq!(Ok(vec![-50.0, 50.0, 0.0]), state.values());
+	assert_eq!(Ok(vec![1]), state.winners());
+
+	let lines = [
+	    vec![Action::Call, Action::Call, Action::Call],
+	    vec![Action::Raise(1000), Action::Raise(5000), Action::Call, Action::Fold],
+	    vec![Action::Raise(300), Action::Raise(20000), Action::Fold, Action::Call],
+	    vec![Action::Raise(20000), Action::Call, Action::Call],
+	];
+	for (seed, line) in lines.iter().enumerate() {
+	    for i in 0..20 {
+		let mut rng = DealerRng::new((seed * 100 + i) as u32);
+		let mut state = get_state();
+		for action in line {
+		    state.do_action(*action).unwrap();
+		}
+		play_until_showdown(&mut state);
+		state.deal_cards(&mut rng);
+		let values = state.values().unwrap();
+		assert_eq!(c_values(&state), values);
+		assert_eq!(0.0, values.iter().sum::<f64>());
+		for p in 0..3 {
+		    assert_eq!(Ok(values[p as usize]), state.value_of_state(p));
+		}
+	    }
+	}
     }
-    
+
     #[test]
-    fn showdown() {
-	let hole_cards = [[1, 35], [5, 50], [11, 51]];
-	let board = [17, 19, 23, 29, 37];
+    fn action_history() {
 	let mut state = get_state();
-	play_until_showdown(&mut state);
-	for (i, cards) in hole_cards.iter().enumerate() {
-	    state.set_hole_cards(i as u8, cards).unwrap();
+	assert_eq!(0, state.action_history().count());
+	let history = vec![
+	    (0, 2, Action::Raise(200)),
+	    (0, 0, Action::Call),
+	    (0, 1, Action::Call),
+	    (1, 0, Action::Call),
+	    (1, 1, Action::Raise(400)),
+	    (1, 2, Action::Fold),
+	    (1, 0, Action::Call),
+	];
+	for (_, _, action) in history.iter() {
+	    state.do_action(*action).unwrap();
 	}
-	for (i, cards) in hole_cards.iter().enumerate() {
-	    assert_eq!(Ok(&cards[..]), state.hole_cards(i as u8));
+	assert_eq!(2, state.get_round());
+	assert_eq!(history, state.action_history().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn spent_at_round_start() {
+	let mut state = get_state();
+	assert_eq!(Ok(vec![50, 100, 0]), state.spent_at_round_start(0));
+	assert_eq!(Err(AcpcError::InvalidRound(1)), state.spent_at_round_start(1));
+	for action in [Action::Raise(300), Action::Call, Action::Call, Action::Raise(1000), Action::Call] {
+	    state.do_action(action).unwrap();
 	}
-	state.set_board_cards(&board);
+	assert_eq!(Ok(vec![50, 100, 0]), state.spent_at_round_start(0));
+	assert_eq!(Ok(vec![300, 300, 300]), state.spent_at_round_start(1));
+	assert_eq!(Err(AcpcError::InvalidRound(2)), state.spent_at_round_start(2));
+	state.do_action(Action::Fold).unwrap();
+	state.do_action(Action::Raise(20000)).unwrap();
+	state.do_action(Action::Call).unwrap();
+	// the hand goes to the river once both players are all in on the turn
+	assert_eq!(3, state.get_round());
+	assert_eq!(Ok(vec![1000, 1000, 300]), state.spent_at_round_start(2));
+	assert_eq!(Ok(vec![20000, 20000, 300]), state.spent_at_round_start(3));
+	assert_eq!(Err(AcpcError::InvalidRound(4)), state.spent_at_round_start(4));
+
+	// undoing and parsing keep the spents of the rounds so far
+	state.deal_cards(&mut DealerRng::new(1));
+	let line = state.to_match_state(0, 0).unwrap();
+	let (_, _, parsed) = State::from_match_state(&state.game, &line).unwrap();
+	for round in 0..4 {
+	    assert_eq!(state.spent_at_round_start(round), parsed.spent_at_round_start(round));
+	}
+	state.undo_action();
+	assert_eq!(2, state.get_round());
+	assert_eq!(Ok(vec![1000, 1000, 300]), state.spent_at_round_start(2));
+	assert_eq!(Err(AcpcError::InvalidRound(3)), state.spent_at_round_start(3));
+    }
+
+    #[test]
+    fn round_betting_string() {
+	let mut state = get_state();
+	assert_eq!(Ok(String::new()), state.round_betting_string(0));
+	for action in [Action::Raise(200), Action::Call, Action::Call,
+		       Action::Call, Action::Raise(400), Action::Raise(1200)] {
+	    state.do_action(action).unwrap();
+	}
+	assert_eq!(Ok("r200cc".to_owned()), state.round_betting_string(0));
+	assert_eq!(Ok("cr400r1200".to_owned()), state.round_betting_string(1));
+	assert_eq!(Ok(String::new()), state.round_betting_string(2));
+	assert_eq!(Err(AcpcError::InvalidRound(4)), state.round_betting_string(4));
+
+	let file = File::open("resources/leduc.limit.2p.game").unwrap();
+	let mut state = State::new(Game::read(file));
+	for action in [Action::Call, Action::Raise(0), Action::Raise(0), Action::Call] {
+	    state.do_action(action).unwrap();
+	}
+	assert_eq!(Ok("crrc".to_owned()), state.round_betting_string(0));
+    }
+
+    #[test]
+    fn replay_betting() {
+	let mut state = get_state();
+	state.replay_betting("r200r1000cc/").unwrap();
+	assert_eq!(&[1000, 1000, 1000], state.spents());
+	assert_eq!(1, state.get_round());
+	assert_eq!(Ok("r200r1000cc".to_owned()), state.round_betting_string(0));
+	state.replay_betting("cr3000").unwrap();
+	assert_eq!(Ok("cr3000".to_owned()), state.round_betting_string(1));
+
+	let mut state = get_state();
+	assert_eq!(Err(AcpcError::ActionToken(ActionError::UnknownAction('x'))),
+		   state.replay_betting("r200x"));
+	assert_eq!(Err(AcpcError::InvalidAction(Action::Raise(50))), state.replay_betting("cr50"));
+	assert_eq!(Err(AcpcError::InvalidRound(1)), state.replay_betting("cc/"));
+	assert_eq!(Err(AcpcError::InvalidRound(0)), state.replay_betting("cccc"));
+	assert_eq!(0, state.num_actions());
+
+	let mut state = State::new(Game::leduc());
+	state.replay_betting("crrc/rc").unwrap();
+	assert!(state.is_finished());
+	assert_eq!(&[9, 9], state.spents());
+    }
+
+    #[test]
+    fn num_actions_in_round() {
+	let mut state = get_state();
+	for action in [Action::Raise(200), Action::Call, Action::Call,
+		       Action::Call, Action::Raise(400)] {
+	    state.do_action(action).unwrap();
+	}
+	assert_eq!(1, state.get_round());
+	assert_eq!(Ok(3), state.num_actions_in_round(0));
+	assert_eq!(Ok(2), state.num_actions_in_round(1));
+	assert_eq!(state.num_actions(), state.num_actions_in_round(1).unwrap());
+	assert_eq!(Ok(0), state.num_actions_in_round(2));
+	assert_eq!(Err(AcpcError::InvalidRound(4)), state.num_actions_in_round(4));
+    }
+
+    #[test]
+    fn players_in_action_order() {
+	let mut state = get_state();
+	assert_eq!(vec![2, 0, 1], state.players_in_action_order());
+	state.do_action(Action::Fold).unwrap();
+	assert_eq!(vec![2, 0, 1], state.players_in_action_order());
+	state.do_action(Action::Call).unwrap();
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(vec![0, 1, 2], state.players_in_action_order());
+    }
+
+    #[test]
+    fn players_to_act() {
+	let mut state = get_state();
+	assert_eq!(vec![2, 0, 1], state.players_to_act());
+	state.do_action(Action::Raise(300)).unwrap();
+	assert_eq!(vec![0, 1], state.players_to_act());
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(vec![1], state.players_to_act());
+	state.do_action(Action::Raise(1000)).unwrap();
+	assert_eq!(vec![2, 0], state.players_to_act());
+	state.do_action(Action::Fold).unwrap();
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(1, state.get_round());
+	assert_eq!(vec![0, 1], state.players_to_act());
+	state.do_action(Action::Raise(20000)).unwrap();
+	assert_eq!(vec![1], state.players_to_act());
+	state.do_action(Action::Call).unwrap();
+	assert!(state.players_to_act().is_empty());
+    }
+
+    #[test]
+    fn round_just_advanced() {
+	let mut state = get_state();
+	assert!(!state.round_just_advanced());
+	let mut advanced = 0;
+	for action in [Action::Raise(200), Action::Call, Action::Call, Action::Raise(400)] {
+	    state.do_action(action).unwrap();
+	    if state.round_just_advanced() {
+		assert_eq!(1, state.get_round());
+		assert_eq!(Ok(0), state.num_actions_in_round(1));
+		advanced += 1;
+	    }
+	}
+	assert_eq!(1, advanced);
+	assert!(!state.round_just_advanced());
+	state.do_action(Action::Fold).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	assert!(state.is_finished());
+	assert!(!state.round_just_advanced());
+    }
+
+    #[test]
+    fn acting_player() {
+	let mut state = get_state();
+	assert_eq!(None, state.last_actor());
+	assert_eq!(Err(AcpcError::InvalidActionIndex(0)), state.acting_player(0, 0));
+	for action in [Action::Raise(200), Action::Call, Action::Call, Action::Raise(400), Action::Call] {
+	    state.do_action(action).unwrap();
+	}
+	assert_eq!(Ok(2), state.acting_player(0, 0));
+	assert_eq!(Ok(0), state.acting_player(0, 1));
+	assert_eq!(Ok(1), state.acting_player(0, 2));
+	assert_eq!(Ok(0), state.acting_player(1, 0));
+	assert_eq!(Ok(1), state.acting_player(1, 1));
+	assert_eq!(Some(1), state.last_actor());
+	assert_eq!(Err(AcpcError::InvalidActionIndex(3)), state.acting_player(0, 3));
+	assert_eq!(Err(AcpcError::InvalidActionIndex(0)), state.acting_player(2, 0));
+	assert_eq!(Err(AcpcError::InvalidRound(4)), state.acting_player(4, 0));
+    }
+
+    #[test]
+    fn is_chance_node() {
+	let mut state = get_state();
+	assert!(state.is_chance_node());
+	for (p, cards) in [[1, 35], [5, 50], [11, 51]].iter().enumerate() {
+	    state.set_hole_cards(p as u8, cards).unwrap();
+	}
+	assert!(!state.is_chance_node());
+	for action in [Action::Call, Action::Call, Action::Call] {
+	    state.do_action(action).unwrap();
+	}
+	assert!(state.is_chance_node());
+	state.set_board_cards(&[17, 19, 23]).unwrap();
+	assert!(!state.is_chance_node());
+	state.do_action(Action::Raise(400)).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	assert!(!state.is_chance_node());
+    }
+
+    #[test]
+    fn set_hole_cards() {
+	let mut state = get_state();
+	assert_eq!(Err(AcpcError::InvalidCard(99)), state.set_hole_cards(0, &[99, 100]));
+	assert_eq!(Err(AcpcError::InvalidCardCount(1)), state.set_hole_cards(0, &[1]));
+	assert_eq!(Err(AcpcError::InvalidCardCount(3)), state.set_hole_cards(0, &[1, 2, 3]));
+	assert_eq!(Err(AcpcError::DuplicateCard(1)), state.set_hole_cards(0, &[1, 1]));
+	assert_eq!(Err(AcpcError::InvalidPlayer(3)), state.set_hole_cards(3, &[1, 2]));
+	assert_eq!(Ok(&[NOT_DEALT; 2][..]), state.hole_cards(0));
+
+	state.set_hole_cards(0, &[1, 35]).unwrap();
+	assert_eq!(Err(AcpcError::DuplicateCard(35)), state.set_hole_cards(1, &[35, 2]));
+	state.set_hole_cards(0, &[35, 1]).unwrap();
+	state.set_board_cards(&[17, 19, 23]).unwrap();
+	assert_eq!(Err(AcpcError::DuplicateCard(19)), state.set_hole_cards(2, &[2, 19]));
+	state.set_hole_cards(2, &[2, 3]).unwrap();
+	assert_eq!(Err(AcpcError::DuplicateCard(35)), state.set_board_cards(&[35, 19, 23]));
+	assert_eq!(Err(AcpcError::DuplicateCard(17)), state.set_board_cards(&[17, 17, 23]));
+	assert_eq!(Err(AcpcError::InvalidCard(NOT_DEALT)), state.set_board_cards(&[17, NOT_DEALT, 23]));
+	assert_eq!(Err(AcpcError::InvalidCard(52)), state.set_board_cards(&[17, 52, 23]));
+	assert_eq!(&[17, 19, 23][..], state.board_cards());
+
+	let file = File::open("resources/leduc.limit.2p.game").unwrap();
+	let mut state = State::new(Game::read(file));
+	let jack = parse_card("Jh").unwrap();
+	assert_eq!(Err(AcpcError::InvalidCard(jack)), state.set_hole_cards(0, &[jack]));
+    }
+
+    #[test]
+    fn board_card() {
+	let mut state = get_state();
+	assert_eq!(0, state.board_cards().len());
+	let board = [17, 19, 23];
+	state.set_board_cards(&board).unwrap();
+	assert_eq!(&board[..], state.board_cards());
+	let board = [17, 19, 23, 24];
+	state.set_board_cards(&board).unwrap();
+	assert_eq!(&board[..], state.board_cards());
+	let board = [17, 19, 23, 24, 25];
+	state.set_board_cards(&board).unwrap();
+	assert_eq!(&board[..], state.board_cards());
+	state.set_board_cards(&[]).unwrap();
+	assert_eq!(0, state.board_cards().len());
+	assert_eq!(Err(AcpcError::InvalidCardCount(2)), state.set_board_cards(&[17, 19]));
+	assert_eq!(Err(AcpcError::InvalidCardCount(6)),
+		   state.set_board_cards(&[17, 19, 23, 24, 25, 26]));
+	assert_eq!(0, state.board_cards().len());
+    }
+    
+    #[test]
+    fn showdown() {
+	let hole_cards = [[1, 35], [5, 50], [11, 51]];
+	let board = [17, 19, 23, 29, 37];
+	let mut state = get_state();
+	play_until_showdown(&mut state);
+	for (i, cards) in hole_cards.iter().enumerate() {
+	    state.set_hole_cards(i as u8, cards).unwrap();
+	}
+	for (i, cards) in hole_cards.iter().enumerate() {
+	    assert_eq!(Ok(&cards[..]), state.hole_cards(i as u8));
+	}
+	state.set_board_cards(&board).unwrap();
 	assert_eq!(&board[..], state.board_cards());
 	// println!("{}", state);
 	assert_eq!(Ok(-100.0), state.value_of_state(0)); // lose
 	assert_eq!(Ok(50.0), state.value_of_state(1)); // tie
 	assert_eq!(Ok(50.0), state.value_of_state(2)); // tie
+	assert_eq!(Ok(vec![1, 2]), state.winners());
+	assert_eq!(Ok(-1.0), state.value_in_bb(0));
+	assert_eq!(Ok(0.5), state.value_in_bb(1));
+	assert_eq!(Ok(0.5), state.value_in_bb(2));
+	assert_eq!(Err(AcpcError::InvalidPlayer(3)), state.value_in_bb(3));
+    }
+
+    #[test]
+    fn board_by_round() {
+	let mut state = get_state();
+	assert!(state.board_by_round().is_empty());
+	state.set_board_cards(&[17, 19, 23]).unwrap();
+	assert_eq!(vec![vec![17, 19, 23]], state.board_by_round());
+	state.set_board_cards(&[17, 19, 23, 29, 37]).unwrap();
+	let groups = state.board_by_round();
+	assert_eq!(vec![3, 1, 1], groups.iter().map(Vec::len).collect::<Vec<_>>());
+	assert_eq!(state.board_cards(), groups.concat());
+    }
+
+    #[test]
+    fn showdown_ranks() {
+	let holes: [&[Card]; 3] = [&[1, 35], &[5, 50], &[11, 51]];
+	let board = [17, 19, 23, 29, 37];
+	let mut state = get_state();
+	play_until_showdown(&mut state);
+	for (i, cards) in holes.iter().enumerate() {
+	    state.set_hole_cards(i as u8, cards).unwrap();
+	}
+	state.set_board_cards(&board).unwrap();
+	let ranks = state.game.showdown_ranks(&holes, &board).unwrap();
+	let best = *ranks.iter().max().unwrap();
+	let winners = (0..3).filter(|&p| ranks[p as usize] == best).collect::<Vec<u8>>();
+	assert_eq!(Ok(winners), state.winners());
+	assert!(ranks[0] < best);
+
+	assert_eq!(Err(AcpcError::DuplicateCard(5)),
+		   state.game.showdown_ranks(&[&[1, 5], &[5, 50]], &board));
+	assert_eq!(Ok(vec![]), state.game.showdown_ranks(&[], &board));
+    }
+
+    #[test]
+    fn from_match_state() {
+	let game = get_state().game;
+	let line = "MATCHSTATE:1:7:r200cc/:|AsKh|/7h8d9c\r\n";
+	let (hand_id, position, state) = State::from_match_state(&game, line).unwrap();
+	assert_eq!(7, hand_id);
+	assert_eq!(1, position);
+	assert_eq!(1, state.get_round());
+	assert_eq!(&[200, 200, 200], state.spents());
+	assert_eq!(Ok(&parse_cards("AsKh").unwrap()[..]), state.hole_cards(1));
+	assert_eq!(Ok(&[NOT_DEALT; 2][..]), state.hole_cards(0));
+	assert_eq!(&parse_cards("7h8d9c").unwrap()[..], state.board_cards());
+	assert_eq!(0, state.current_player());
+    }
+
+    #[test]
+    fn from_match_state_invalid() {
+	let game = get_state().game;
+	for line in ["",
+		     "STATE:7:r200cc/:|AsKh|/7h8d9c",
+		     "MATCHSTATE:3:7:r200cc/:|AsKh|/7h8d9c",
+		     "MATCHSTATE:1:7:r100cc/:|AsKh|/7h8d9c",
+		     "MATCHSTATE:1:7:r200cc/:|AsKh|/7h8d",
+		     "MATCHSTATE:1:7:r200cc/:|AsKh|/7h8d9c:junk"] {
+	    assert_eq!(Err(ParseError::InvalidMatchState(line.to_owned())),
+		       State::from_match_state(&game, line).map(|_| ()));
+	}
+    }
+
+    #[test]
+    fn to_match_state() {
+	let game = get_state().game;
+	let line = "MATCHSTATE:1:7:r200cc/:|AsKh|/7h8d9c";
+	let (_, _, mut state) = State::from_match_state(&game, line).unwrap();
+	assert_eq!(Ok(line.to_owned()), state.to_match_state(1, 7));
+
+	state.set_hole_cards(0, &parse_cards("2c2d").unwrap()).unwrap();
+	assert_eq!(Ok("MATCHSTATE:0:8:r200cc/:2c2d||/7h8d9c".to_owned()),
+		   state.to_match_state(0, 8));
+	assert!(state.to_match_state(3, 7).is_err());
+
+	let mut state = get_state();
+	assert!(state.to_match_state(2, 0).is_err());
+	state.set_hole_cards(2, &parse_cards("AsKh").unwrap()).unwrap();
+	assert_eq!(Ok("MATCHSTATE:2:0::||AsKh".to_owned()), state.to_match_state(2, 0));
+    }
+
+    #[test]
+    fn to_log_line() {
+	let mut state = get_state();
+	assert_eq!(Err(AcpcError::GameNotFinished), state.to_log_line(0));
+	play_until_showdown(&mut state);
+	state.set_hole_cards(0, &parse_cards("2dTs").unwrap()).unwrap();
+	state.set_hole_cards(1, &parse_cards("3dAh").unwrap()).unwrap();
+	assert_eq!(Err(AcpcError::CardsNotDealt), state.to_log_line(0));
+	state.set_hole_cards(2, &parse_cards("4sAs").unwrap()).unwrap();
+	state.set_board_cards(&parse_cards("6d6s7s9dJd").unwrap()).unwrap();
+	assert_eq!(Ok("STATE:12:ccc/ccc/ccc/ccc:2dTs|3dAh|4sAs/6d6s7s/9d/Jd:-100|50|50".to_owned()),
+		   state.to_log_line(12));
+
+	let mut state = get_state();
+	state.do_action(Action::Raise(200)).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	state.deal_cards(&mut DealerRng::new(0));
+	let line = state.to_log_line(1).unwrap();
+	assert!(line.starts_with("STATE:1:r200ff:"));
+	assert!(line.ends_with(":-50|-100|150"));
+    }
+
+    #[test]
+    fn from_log_line() {
+	let game = get_state().game;
+	let line = "STATE:12:r300r900cf/cr2000c/cc/r20000f:2dTs|3dAh|4sAs/6d6s7s/9d/Jd:2300|-2000|-300";
+	let state = State::from_log_line(&game, line).unwrap();
+	assert_eq!(12, state.hand_id());
+	assert_eq!(Ok(vec![2300.0, -2000.0, -300.0]), state.values());
+	assert_eq!(Ok(line.to_owned()), state.to_log_line(12));
+	assert_eq!(Ok(&parse_cards("3dAh").unwrap()[..]), state.hole_cards(1));
+	let with_names = format!("{}:alice|bob|carol\n", line);
+	assert_eq!(Ok(line.to_owned()), State::from_log_line(&game, &with_names).unwrap().to_log_line(12));
+
+	let mut state = get_state();
+	state.random_rollout(&mut DealerRng::new(3)).unwrap();
+	let line = state.to_log_line(4).unwrap();
+	assert_eq!(Ok(line.clone()), State::from_log_line(&game, &line).unwrap().to_log_line(4));
+
+	let file = File::open("resources/leduc.limit.2p.game").unwrap();
+	let leduc = Game::read(file);
+	let line = "STATE:0:rrc/crrc:Qh|Ks/Ah:-13|13";
+	let state = State::from_log_line(&leduc, line).unwrap();
+	assert_eq!(&[13, 13], state.spents());
+	assert_eq!(Ok(line.to_owned()), state.to_log_line(0));
+
+	for invalid in ["STATE:12:r300r900cf/cr2000c/cc/r20000f:2dTs|3dAh|4sAs/6d6s7s/9d/Jd",
+			"STATE:12:r300r900cf/cr2000c/cc/r20000f:2dTs|3dAh|4sAs/6d6s7s/9d/Jd:-300|-2000",
+			"STATE:12:r300r900cf/cr2000c/cc/r20000f:2dTs|3dAh|4sAs/6d6s7s/9d/Jd:a|b|c",
+			"STATE:12:r300r900cf/cr2000c:2dTs|3dAh|4sAs/6d6s7s:0|0|0",
+			"MATCHSTATE:0:12:r300r900cf:2dTs||:0|0|0"] {
+	    assert_eq!(Err(ParseError::InvalidLogLine(invalid.to_owned())),
+		       State::from_log_line(&game, invalid).map(|_| ()));
+	}
+    }
+
+    #[test]
+    fn exact_equity() {
+	let file = File::open("resources/leduc.limit.2p.game").unwrap();
+	let mut state = State::new(Game::read(file));
+	state.set_hole_cards(0, &parse_cards("Kh").unwrap()).unwrap();
+	// 20 ways to deal the other card and the board: Kh wins 8 and ties 4
+	assert_eq!(Ok(vec![0.5, 0.5]), state.exact_equity());
+	state.set_hole_cards(1, &parse_cards("Qh").unwrap()).unwrap();
+	// Kh loses only to the Qs on the board
+	assert_eq!(Ok(vec![0.75, 0.25]), state.exact_equity());
+	state.set_board_cards(&parse_cards("Qs").unwrap()).unwrap();
+	assert_eq!(Ok(vec![0.0, 1.0]), state.exact_equity());
+
+	let mut state = get_state();
+	assert!(matches!(state.exact_equity(), Err(AcpcError::TooManyCompletions(_))));
+	state.set_hole_cards(0, &parse_cards("AsAh").unwrap()).unwrap();
+	state.set_hole_cards(1, &parse_cards("7c2d").unwrap()).unwrap();
+	state.set_hole_cards(2, &parse_cards("KdKc").unwrap()).unwrap();
+	// 46 choose 5 boards
+	assert_eq!(Err(AcpcError::TooManyCompletions(1_370_754)), state.exact_equity());
+	// of the 43 choose 2 turns and rivers, only the two other aces give the
+	// aces four of a kind over the kings
+	state.set_board_cards(&parse_cards("KhKs2c").unwrap()).unwrap();
+	assert_eq!(Ok(vec![1.0 / 903.0, 0.0, 902.0 / 903.0]), state.exact_equity());
+	state.set_board_cards(&parse_cards("Kh8d3h7s").unwrap()).unwrap();
+	let equity = state.exact_equity().unwrap();
+	assert!((equity.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+	// only the two other aces on the river beat the kings
+	let river = state.remaining_deck().len() as f64;
+	assert_eq!(vec![2.0 / river, 0.0, (river - 2.0) / river], equity);
+    }
+
+    #[test]
+    fn deal_cards() {
+	let mut rng = DealerRng::new(42);
+	let mut state = get_state();
+	state.deal_cards(&mut rng);
+	assert_eq!(0, state.board_cards().len());
+	let hole_cards = (0..3).map(|p| state.hole_cards(p).unwrap().to_vec())
+	    .collect::<Vec<_>>();
+
+	play_until_showdown(&mut state);
+	state.deal_cards(&mut rng);
+	assert_eq!(5, state.board_cards().len());
+	let mut cards = state.board_cards().to_vec();
+	for p in 0..3 {
+	    assert_eq!(Ok(&hole_cards[p as usize][..]), state.hole_cards(p));
+	    cards.extend_from_slice(state.hole_cards(p).unwrap());
+	}
+	assert_eq!(11, cards.len());
+	assert!(cards.iter().all(|&c| c < 52));
+	cards.sort();
+	cards.dedup();
+	assert_eq!(11, cards.len());
+    }
+
+    #[test]
+    fn remaining_deck() {
+	let mut state = get_state();
+	assert_eq!(52, state.remaining_deck().len());
+	state.set_hole_cards(0, &parse_cards("AsKh").unwrap()).unwrap();
+	for action in [Action::Call, Action::Call, Action::Call] {
+	    state.do_action(action).unwrap();
+	}
+	state.set_board_cards(&parse_cards("7h8d9c").unwrap()).unwrap();
+	let deck = state.remaining_deck();
+	assert_eq!(47, deck.len());
+	for card in parse_cards("AsKh7h8d9c").unwrap() {
+	    assert!(!deck.contains(&card));
+	}
+	assert!(deck.contains(&parse_card("Ah").unwrap()));
+
+	let file = File::open("resources/leduc.limit.2p.game").unwrap();
+	let mut state = State::new(Game::read(file));
+	assert_eq!(parse_cards("Qh Kh Ah Qs Ks As"), Ok(state.remaining_deck()));
+	state.set_hole_cards(1, &parse_cards("Kh").unwrap()).unwrap();
+	assert_eq!(parse_cards("Qh Ah Qs Ks As"), Ok(state.remaining_deck()));
+    }
+
+    #[test]
+    fn deal_cards_seeded() {
+	let deal = |seed| {
+	    let mut state = get_state();
+	    play_until_showdown(&mut state);
+	    state.deal_cards_seeded(seed);
+	    let mut cards = state.board_cards().to_vec();
+	    for p in 0..3 {
+		cards.extend_from_slice(state.hole_cards(p).unwrap());
+	    }
+	    cards
+	};
+	assert_eq!(11, deal(7).len());
+	assert_eq!(deal(7), deal(7));
+	assert_eq!(deal(1 << 40), deal(1 << 40));
+	assert_ne!(deal(7), deal(8));
+	assert_ne!(deal(7), deal(7 | 1 << 40));
+    }
+
+    #[test]
+    fn dealer_rng_threads() {
+	let sequence = |mut rng: DealerRng| (0..1000).map(|_| rng.next_u32()).collect::<Vec<_>>();
+	let threads = (0..2)
+	    .map(|_| std::thread::spawn(move || sequence(DealerRng::new(42))))
+	    .collect::<Vec<_>>();
+	let sequences = threads.into_iter().map(|t| t.join().unwrap()).collect::<Vec<_>>();
+	assert_eq!(sequences[0], sequences[1]);
+	assert_eq!(sequence(DealerRng::new(42)), sequences[0]);
+
+	let deal = std::thread::spawn(|| {
+	    let mut state = get_state();
+	    state.deal_cards(&mut DealerRng::new(7));
+	    state.hole_cards(0).unwrap().to_vec()
+	});
+	let mut state = get_state();
+	state.deal_cards(&mut DealerRng::new(7));
+	assert_eq!(state.hole_cards(0).unwrap(), &deal.join().unwrap()[..]);
+    }
+
+    #[test]
+    fn deal_cards_keeps_set_cards() {
+	let mut rng = DealerRng::new(0);
+	let mut state = get_state();
+	state.set_hole_cards(0, &[1, 35]).unwrap();
+	state.set_board_cards(&[17, 19, 23]).unwrap();
+	play_until_showdown(&mut state);
+	state.deal_cards(&mut rng);
+	assert_eq!(Ok(&[1, 35][..]), state.hole_cards(0));
+	assert_eq!(&[17, 19, 23], &state.board_cards()[..3]);
+	let mut cards = state.board_cards().to_vec();
+	for p in 0..3 {
+	    cards.extend_from_slice(state.hole_cards(p).unwrap());
+	}
+	cards.sort();
+	cards.dedup();
+	assert_eq!(11, cards.len());
+    }
+
+    #[test]
+    fn hand_id() {
+	let file = File::open("resources/holdem.nolimit.3p.game").unwrap();
+	let mut state = State::new_with_hand_id(Game::read(file), 42);
+	assert_eq!(42, state.hand_id());
+	assert!(state.to_string().starts_with("STATE:42:"));
+	state.set_hole_cards(0, &parse_cards("AsKh").unwrap()).unwrap();
+	assert_eq!(Ok("MATCHSTATE:0:42::AsKh||".to_owned()),
+		   state.to_match_state(0, state.hand_id()));
+	state.set_hand_id(43);
+	assert_eq!(43, state.hand_id());
+	assert!(state.to_string().starts_with("STATE:43:"));
+	assert_eq!(0, get_state().hand_id());
+    }
+
+    #[test]
+    fn reset() {
+	let fresh = get_state();
+	let mut state = get_state();
+	state.set_hole_cards(0, &parse_cards("AsKh").unwrap()).unwrap();
+	state.do_action(Action::Raise(200)).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	assert!(state.is_finished());
+	state.reset(1);
+	assert!(!state.is_finished());
+	assert_eq!(1, state.hand_id());
+	assert_eq!(fresh.current_player(), state.current_player());
+	assert_eq!(fresh.spents(), state.spents());
+	assert_eq!(fresh.get_round(), state.get_round());
+	assert_eq!(fresh.num_actions(), state.num_actions());
+	assert_eq!(fresh.num_folded(), state.num_folded());
+	assert_eq!(fresh.legal_actions(), state.legal_actions());
+	assert_eq!(fresh.hole_cards(0), state.hole_cards(0));
+	assert_eq!(fresh.to_string(), state.to_string().replace("STATE:1:", "STATE:0:"));
+    }
+
+    #[test]
+    fn invariants() {
+	let mut state = get_state();
+	state.check_invariants();
+	for action in [Action::Raise(200), Action::Call, Action::Call,
+		       Action::Raise(1000), Action::Fold, Action::Call,
+		       Action::Call, Action::Raise(20000), Action::Call] {
+	    state.do_action(action).unwrap();
+	    state.check_invariants();
+	}
+	assert!(state.is_finished());
+    }
+
+    #[test]
+    fn play_out() {
+	let mut expected = get_state();
+	while !expected.is_finished() {
+	    expected.do_action(Action::Call).unwrap();
+	}
+	let mut state = get_state();
+	let mut players = vec![];
+	state.play_out(|s| {
+	    players.push(s.current_player());
+	    Action::Call
+	}).unwrap();
+	assert_eq!(expected.to_string(), state.to_string());
+	assert_eq!(vec![2, 0, 1, 0, 1, 2, 0, 1, 2, 0, 1, 2], players);
+
+	let mut state = get_state();
+	assert_eq!(Err(AcpcError::InvalidAction(Action::Raise(1))),
+		   state.play_out(|_| Action::Raise(1)));
+	assert_eq!(0, state.num_actions());
+	// the failed play out leaves the state usable
+	state.play_out(|_| Action::Call).unwrap();
+	assert!(state.is_finished());
+    }
+
+    #[test]
+    fn random_rollout() {
+	let rollout = |seed| {
+	    let mut state = get_state();
+	    let values = state.random_rollout(&mut DealerRng::new(seed)).unwrap();
+	    (state.action_history().collect::<Vec<_>>(), values, state.board_cards().to_vec())
+	};
+	assert_eq!(rollout(3), rollout(3));
+	let (history, values, _) = rollout(3);
+	assert_eq!(0.0, values.iter().sum::<f64>());
+	assert!(!history.is_empty());
+	assert!((0..20).map(rollout).any(|(h, _, _)| h != history));
+    }
+
+    #[test]
+    fn apply() {
+	let state = get_state();
+	let next = state.apply(Action::Raise(200)).unwrap();
+	assert_eq!(&[50, 100, 0], state.spents());
+	assert_eq!(0, state.num_actions());
+	let mut expected = state.clone();
+	expected.do_action(Action::Raise(200)).unwrap();
+	assert_eq!(expected.to_string(), next.to_string());
+	assert_eq!(expected.spents(), next.spents());
+	assert_eq!(expected.current_player(), next.current_player());
+	assert_eq!(Err(AcpcError::InvalidAction(Action::Raise(100))),
+		   next.apply(Action::Raise(100)).map(|_| ()));
+    }
+
+    #[test]
+    fn clone_into() {
+	let mut state = get_state();
+	for action in [Action::Raise(200), Action::Call, Action::Call, Action::Raise(1000)] {
+	    state.do_action(action).unwrap();
+	}
+	state.deal_cards(&mut DealerRng::new(5));
+	let file = File::open("resources/leduc.limit.2p.game").unwrap();
+	let mut dst = State::new_with_hand_id(Game::read(file), 9);
+	state.clone_into(&mut dst);
+	assert_eq!(format!("{:?}", state.clone()), format!("{:?}", dst));
+	assert_eq!(state.game, dst.game);
+	assert_eq!(state.hand_id(), dst.hand_id());
+	dst.do_action(Action::Call).unwrap();
+	assert_eq!(1, state.num_actions());
+	assert_eq!(2, dst.num_actions());
+    }
+
+    #[test]
+    fn undo_action() {
+	let mut state = get_state();
+	state.set_hole_cards(0, &parse_cards("AsKh").unwrap()).unwrap();
+	assert_eq!(None, state.undo_action());
+	let start = state.to_string();
+	state.do_action(Action::Raise(200)).unwrap();
+	let raised = state.clone();
+	for action in [Action::Call, Action::Call, Action::Raise(600), Action::Fold] {
+	    state.do_action(action).unwrap();
+	}
+	assert_eq!(1, state.get_round());
+	assert_eq!(Some(Action::Fold), state.undo_action());
+	assert_eq!(Some(Action::Raise(600)), state.undo_action());
+	assert_eq!(Some(Action::Call), state.undo_action());
+	assert_eq!(0, state.get_round());
+	assert_eq!(Some(Action::Call), state.undo_action());
+	assert_eq!(raised.to_string(), state.to_string());
+	assert_eq!(raised.spents(), state.spents());
+	assert_eq!(raised.current_player(), state.current_player());
+	assert_eq!(Some(Action::Raise(200)), state.undo_action());
+	assert_eq!(start, state.to_string());
+	assert_eq!(Ok(&parse_cards("AsKh").unwrap()[..]), state.hole_cards(0));
+    }
+
+    fn snapshot(state: &State) -> (String, Vec<i32>, u8, bool, u8, u8) {
+	(state.to_string(), state.spents().to_vec(), state.get_round(),
+	 state.is_finished(), state.num_folded(), state.current_player())
+    }
+
+    fn traverse_with_undo(state: &mut State, count: &mut usize) {
+	if state.is_finished() {
+	    *count += 1;
+	    return;
+	}
+	for action in [Action::Fold, Action::Call, Action::Raise(0)] {
+	    if !state.is_valid_action(action) {
+		continue;
+	    }
+	    let before = snapshot(state);
+	    let mut cloned = state.clone();
+	    cloned.do_action(action).unwrap();
+	    state.do_action(action).unwrap();
+	    assert_eq!(snapshot(&cloned), snapshot(state));
+	    traverse_with_undo(state, count);
+	    assert_eq!(Some(action), state.undo_action().map(|a| match a {
+		Action::Raise(_) => Action::Raise(0),
+		a => a,
+	    }));
+	    assert_eq!(before, snapshot(state));
+	}
+    }
+
+    #[test]
+    fn undo_action_traversal() {
+	let file = File::open("resources/leduc.limit.2p.game").unwrap();
+	let mut state = State::new(Game::read(file));
+	let mut count = 0;
+	traverse_with_undo(&mut state, &mut count);
+	assert!(count > 0);
+	assert_eq!(0, state.num_actions());
+    }
+
+    #[test]
+    fn legal_actions() {
+	let mut state = get_state();
+	assert_eq!(vec![Action::Fold, Action::Call, Action::Raise(200), Action::Raise(20000)],
+		   state.legal_actions());
+	state.do_action(Action::Raise(20000)).unwrap();
+	assert_eq!(vec![Action::Fold, Action::Call], state.legal_actions());
+	state.do_action(Action::Fold).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	assert!(state.is_finished());
+	assert_eq!(Vec::<Action>::new(), state.legal_actions());
+
+	let file = File::open("resources/leduc.limit.2p.game").unwrap();
+	let mut state = State::new(Game::read(file));
+	assert_eq!(vec![Action::Call, Action::Raise(2)], state.legal_actions());
+	state.do_action(Action::Raise(2)).unwrap();
+	state.do_action(Action::Raise(2)).unwrap();
+	assert_eq!(vec![Action::Fold, Action::Call], state.legal_actions());
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(vec![Action::Call, Action::Raise(4)], state.legal_actions());
+    }
+
+    #[test]
+    fn info_set_key() {
+	let mut state = get_state();
+	state.do_action(Action::Raise(200)).unwrap();
+	state.do_action(Action::Call).unwrap();
+	state.do_action(Action::Call).unwrap();
+	state.set_hole_cards(0, &parse_cards("AsKh").unwrap()).unwrap();
+	state.set_hole_cards(1, &parse_cards("2c3d").unwrap()).unwrap();
+	state.set_board_cards(&parse_cards("7h8d9c").unwrap()).unwrap();
+
+	let mut other = state.clone();
+	assert_eq!(state.info_set_key(0), other.info_set_key(0));
+	other.set_hole_cards(0, &parse_cards("KhAs").unwrap()).unwrap();
+	other.set_hole_cards(1, &parse_cards("QcQd").unwrap()).unwrap();
+	assert_eq!(state.info_set_key(0), other.info_set_key(0));
+	assert_ne!(state.info_set_key(1), other.info_set_key(1));
+	assert_ne!(state.info_set_key(0), state.info_set_key(2));
+
+	other.set_board_cards(&parse_cards("7h8d9c").unwrap()).unwrap();
+	other.do_action(Action::Raise(400)).unwrap();
+	assert_ne!(state.info_set_key(0), other.info_set_key(0));
+	assert_eq!(Err(AcpcError::InvalidPlayer(3)), state.info_set_key(3));
+    }
+
+    struct Constant;
+
+    impl CardAbstraction for Constant {
+	fn bucket(&self, _game: &Game, _hole: &[Card], _board: &[Card]) -> u32 {
+	    7
+	}
+    }
+
+    #[test]
+    fn info_set_with() {
+	let mut state = get_state();
+	state.do_action(Action::Raise(200)).unwrap();
+	state.set_hole_cards(0, &parse_cards("AsKh").unwrap()).unwrap();
+	state.set_hole_cards(1, &parse_cards("2c3d").unwrap()).unwrap();
+	let mut expected = vec![0, 0, b'r'];
+	expected.extend_from_slice(&200i32.to_le_bytes());
+	expected.push(b'|');
+	expected.extend_from_slice(&7u32.to_le_bytes());
+	assert_eq!(Ok(expected), state.info_set_with(0, &Constant));
+	assert_eq!(state.info_set_with(0, &Constant).map(|k| k[1..].to_vec()),
+		   state.info_set_with(1, &Constant).map(|k| k[1..].to_vec()));
+	assert_ne!(state.info_set_with(0, &IdentityAbstraction),
+		   state.info_set_with(1, &IdentityAbstraction).map(|mut k| { k[0] = 0; k }));
+	assert_eq!(Err(AcpcError::InvalidPlayer(3)), state.info_set_with(3, &Constant));
+    }
+}
+
+#[cfg(test)]
+mod read_match_states_tests {
+    use super::*;
+    use std::fs::File;
+
+    const LOG: &str = "\
+# match log
+MATCHSTATE:0:0::Qh|
+
+MATCHSTATE:0:0:r:Qh|
+MATCHSTATE:0:0:rc/:Qh|/Ks
+; comment
+MATCHSTATE:1:1::|Ah
+MATCHSTATE:1:1:c:|Ah
+MATCHSTATE:1:1:cc/r:|Ah/Qs
+";
+
+    #[test]
+    fn read() {
+	let game = Game::read(File::open("resources/leduc.limit.2p.game").unwrap());
+	let states = read_match_states(&game, LOG.as_bytes())
+	    .collect::<Result<Vec<_>, _>>()
+	    .unwrap();
+	assert_eq!(6, states.len());
+	let ids = states.iter().map(|(id, pos, _)| (*id, *pos)).collect::<Vec<_>>();
+	assert_eq!(vec![(0, 0), (0, 0), (0, 0), (1, 1), (1, 1), (1, 1)], ids);
+	assert_eq!(1, states[2].2.get_round());
+	assert_eq!(&parse_cards("Ks").unwrap()[..], states[2].2.board_cards());
+	assert_eq!(Some(0), states[4].2.last_actor());
+	assert_eq!(Ok(1), states[5].2.num_actions_in_round(1));
+    }
+
+    #[test]
+    fn read_invalid() {
+	let game = Game::read(File::open("resources/leduc.limit.2p.game").unwrap());
+	let log = "MATCHSTATE:0:0::Qh|\nMATCHSTATE:0:0:x:Qh|\n";
+	let states = read_match_states(&game, log.as_bytes()).collect::<Vec<_>>();
+	assert_eq!(2, states.len());
+	assert!(states[0].is_ok());
+	assert!(states[1].is_err());
     }
 }
 
@@ -655,6 +3822,36 @@ mod state_tests_2p {
 	state.do_action(Action::Raise(12000)).unwrap(); // 1
     }
 
+    #[test]
+    fn display_long_history() {
+	let mut state = get_state();
+	let mut betting = String::new();
+	while let Ok((min_size, _)) = state.raise_size() {
+	    state.do_action(Action::Raise(min_size)).unwrap();
+	    betting.push_str(&format!("r{}", min_size));
+	}
+	state.do_action(Action::Call).unwrap();
+	betting.push('c');
+	let string = state.to_string();
+	assert!(string.starts_with(&format!("STATE:0:{}/:", betting)));
+    }
+
+    #[test]
+    fn print_to_string_grows() {
+	let long = "r20000".repeat(1000);
+	let string = print_to_string(|max_len, ptr| {
+	    if (max_len as usize) <= long.len() {
+		return -1;
+	    }
+	    unsafe {
+		std::ptr::copy_nonoverlapping(long.as_ptr(), ptr as *mut u8, long.len());
+	    }
+	    long.len() as i32
+	});
+	assert_eq!(Some(long.clone()), string);
+	assert_eq!(None, print_to_string(|_, _| -1));
+    }
+
     #[test]
     fn is_finished() {
 	let mut state = get_state();
@@ -675,4 +3872,152 @@ mod state_tests_2p {
 	println!("acting {}", state.num_acting_player());
 	assert_eq!(true, state.is_finished());
     }
+
+    #[test]
+    fn ensure_finished() {
+	let mut state = get_state();
+	assert_eq!(Err(AcpcError::GameNotFinished), state.ensure_finished());
+	assert_eq!(state.ensure_finished().map(|_| 0.0), state.value_of_state(0));
+	assert!(state.require_finished().is_err());
+	state.do_action(Action::Fold).unwrap();
+	assert_eq!(Ok(()), state.ensure_finished());
+	assert_eq!(Ok(state.hand_id()), state.require_finished().map(State::hand_id));
+    }
+
+    #[test]
+    fn is_all_in() {
+	let mut state = get_state();
+	assert_eq!(Ok(false), state.is_all_in(1));
+	state.do_action(Action::Raise(20000)).unwrap(); // p1 All-in
+	assert_eq!(Ok(true), state.is_all_in(1));
+	assert_eq!(Ok(false), state.is_all_in(0));
+	assert_eq!(1, state.num_all_in());
+	state.do_action(Action::Call).unwrap(); // p0 Call
+	assert_eq!(Ok(true), state.is_all_in(0));
+	assert_eq!(Err(AcpcError::InvalidPlayer(2)), state.is_all_in(2));
+    }
+
+    #[test]
+    fn is_active() {
+	let mut state = get_state();
+	assert_eq!(Ok(true), state.is_active(0));
+	assert_eq!(Ok(true), state.is_active(1));
+	state.do_action(Action::Raise(20000)).unwrap(); // p1 All-in
+	assert_eq!(Ok(true), state.is_active(0));
+	assert_eq!(Ok(false), state.is_active(1));
+	state.do_action(Action::Fold).unwrap(); // p0 Fold
+	assert_eq!(Ok(false), state.is_active(0));
+	assert_eq!(Err(AcpcError::InvalidPlayer(2)), state.is_active(2));
+
+	let mut state = get_state();
+	state.do_action(Action::Raise(300)).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	assert_eq!(Ok(false), state.is_active(0));
+	assert_eq!(Ok(true), state.is_active(1));
+    }
+
+    #[test]
+    fn terminal_kind() {
+	let mut state = get_state();
+	assert_eq!(None, state.terminal_kind());
+	state.do_action(Action::Raise(300)).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	assert_eq!(Some(TerminalKind::FoldOut { winner: 1 }), state.terminal_kind());
+
+	let mut state = get_state();
+	state.do_action(Action::Raise(20000)).unwrap();
+	assert_eq!(None, state.terminal_kind());
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(Some(TerminalKind::Showdown), state.terminal_kind());
+    }
+
+    #[test]
+    fn players_in_action_order() {
+	let mut state = get_state();
+	assert_eq!(vec![1, 0], state.players_in_action_order());
+	assert_eq!(state.current_player(), state.players_in_action_order()[0]);
+	state.do_action(Action::Call).unwrap();
+	state.do_action(Action::Call).unwrap();
+	assert_eq!(vec![0, 1], state.players_in_action_order());
+	assert_eq!(state.current_player(), state.players_in_action_order()[0]);
+    }
+
+    #[test]
+    fn effective_stack() {
+	let mut state = get_state();
+	state.do_action(Action::Raise(300)).unwrap(); // 1
+	state.do_action(Action::Raise(1000)).unwrap(); // 0
+	assert_eq!(Ok(19700), state.stack_behind(1));
+	assert_eq!(19000, state.effective_stack());
+	state.do_action(Action::Raise(20000)).unwrap(); // 1 all-in
+	assert_eq!(19000, state.effective_stack());
+	state.do_action(Action::Call).unwrap(); // 0 all-in
+	assert_eq!(0, state.effective_stack());
+    }
+
+    #[test]
+    fn estimate_equity() {
+	let mut rng = DealerRng::new(1);
+	let mut state = get_state();
+	state.set_hole_cards(0, &parse_cards("AsAh").unwrap()).unwrap();
+	state.set_hole_cards(1, &parse_cards("7c2d").unwrap()).unwrap();
+	state.set_board_cards(&parse_cards("Kc8d3h").unwrap()).unwrap();
+	let equity = state.estimate_equity(1000, &mut rng).unwrap();
+	assert!(equity[0] > 0.7, "{:?}", equity);
+	assert!((equity[0] + equity[1] - 1.0).abs() < 1e-9);
+	assert_eq!(&parse_cards("Kc8d3h").unwrap()[..], state.board_cards());
+
+	let mut state = get_state();
+	state.set_hole_cards(1, &parse_cards("AsAh").unwrap()).unwrap();
+	let equity = state.estimate_equity(1000, &mut rng).unwrap();
+	assert!(equity[1] > 0.7, "{:?}", equity);
+	state.do_action(Action::Fold).unwrap(); // p1
+	assert_eq!(Ok(vec![1.0, 0.0]), state.estimate_equity(10, &mut rng));
+    }
+
+    #[test]
+    fn action_terminates() {
+	let mut state = get_state();
+	assert_eq!(Ok(false), state.action_terminates(Action::Call));
+	assert_eq!(Ok(true), state.action_terminates(Action::Fold));
+	state.do_action(Action::Raise(300)).unwrap();
+	assert_eq!(Ok(true), state.action_terminates(Action::Fold));
+	assert_eq!(Ok(false), state.action_terminates(Action::Call));
+	assert_eq!(Ok(false), state.action_terminates(Action::Raise(20000)));
+	assert_eq!(1, state.num_actions());
+	state.do_action(Action::Raise(20000)).unwrap();
+	assert_eq!(Ok(true), state.action_terminates(Action::Call));
+	assert_eq!(Err(AcpcError::InvalidAction(Action::Raise(100))),
+		   state.action_terminates(Action::Raise(100)));
+    }
+
+    #[test]
+    fn max_possible_pot() {
+	let mut state = get_state();
+	assert_eq!(state.game.total_money(), state.max_possible_pot());
+	state.do_action(Action::Raise(300)).unwrap();
+	assert_eq!(40000, state.max_possible_pot());
+	state.do_action(Action::Fold).unwrap();
+	assert_eq!(state.pot_size() as i64, state.max_possible_pot());
+
+	// the short stack caps what the big stack can put in
+	let game = GameBuilder::new(BettingType::NoLimit, 2, 4)
+	    .stack(&[20000, 5000])
+	    .blind(&[100, 50])
+	    .first_player(&[1, 0, 0, 0])
+	    .deck(4, 13)
+	    .num_hole_cards(2)
+	    .num_board_cards(&[0, 3, 1, 1])
+	    .build()
+	    .unwrap();
+	assert_eq!(10000, State::new(game).max_possible_pot());
+
+	// with 3 players, the chips which can still go in are those of the
+	// players who did not fold
+	let file = File::open("resources/holdem.nolimit.3p.game").unwrap();
+	let mut state = State::new(Game::read(file));
+	state.do_action(Action::Raise(300)).unwrap();
+	state.do_action(Action::Fold).unwrap();
+	assert_eq!(state.game.total_money() - (20000 - 50), state.max_possible_pot());
+    }
 }
@@ -0,0 +1,304 @@
+//! Serde support, enabled by the `serde` feature.
+//!
+//! Games are stored as their parsed fields so that the `.game` file does not
+//! need to be shipped along. States are stored as the betting history and
+//! the cards instead of the raw C struct, and are rebuilt by replaying the
+//! actions on deserialize.
+
+use acpc_server_sys as acpc;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{card_to_string, parse_card, Action, BettingType, Card, Game, GameBuilder, State, NOT_DEALT};
+
+
+/// Version of the serialized layout.
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct GameRepr {
+    betting_type: String,
+    num_players: u8,
+    num_rounds: u8,
+    stack: Vec<i32>,
+    blind: Vec<i32>,
+    raise_size: Vec<i32>,
+    first_player: Vec<u8>,
+    max_raises: Vec<u8>,
+    num_suits: u8,
+    num_ranks: u8,
+    num_hole_cards: u8,
+    num_board_cards: Vec<u8>,
+}
+
+impl GameRepr {
+    fn new(game: &Game) -> Self {
+	let g = &game.game_;
+	let num_players = g.numPlayers as usize;
+	let num_rounds = g.numRounds as usize;
+	let betting_type = match g.bettingType {
+	    acpc::BettingType_limitBetting => "limit",
+	    _ => "nolimit",
+	};
+	// `readGame` leaves the raise sizes of no-limit games uninitialized
+	let raise_size = match g.bettingType {
+	    acpc::BettingType_limitBetting => g.raiseSize[..num_rounds].to_vec(),
+	    _ => vec![0; num_rounds],
+	};
+	GameRepr {
+	    betting_type: betting_type.to_owned(),
+	    num_players: g.numPlayers,
+	    num_rounds: g.numRounds,
+	    stack: g.stack[..num_players].to_vec(),
+	    blind: g.blind[..num_players].to_vec(),
+	    raise_size,
+	    first_player: g.firstPlayer[..num_rounds].to_vec(),
+	    max_raises: g.maxRaises[..num_rounds].to_vec(),
+	    num_suits: g.numSuits,
+	    num_ranks: g.numRanks,
+	    num_hole_cards: g.numHoleCards,
+	    num_board_cards: g.numBoardCards[..num_rounds].to_vec(),
+	}
+    }
+
+    fn into_game(self) -> Result<Game, String> {
+	let betting_type = match self.betting_type.as_str() {
+	    "limit" => BettingType::Limit,
+	    "nolimit" => BettingType::NoLimit,
+	    s => return Err(format!("Invalid betting type {:?}", s)),
+	};
+	GameBuilder::new(betting_type, self.num_players, self.num_rounds)
+	    .stack(&self.stack)
+	    .blind(&self.blind)
+	    .raise_size(&self.raise_size)
+	    .first_player(&self.first_player)
+	    .max_raises(&self.max_raises)
+	    .deck(self.num_suits, self.num_ranks)
+	    .num_hole_cards(self.num_hole_cards)
+	    .num_board_cards(&self.num_board_cards)
+	    .build()
+	    .map_err(|e| e.to_string())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct StateRepr {
+    version: u32,
+    game: GameRepr,
+    hand_id: u32,
+    round: u8,
+    finished: bool,
+    /// ACPC action tokens of each round.
+    actions: Vec<Vec<String>>,
+    board_cards: Vec<String>,
+    /// Hole cards of each player, empty if not dealt.
+    hole_cards: Vec<Vec<String>>,
+}
+
+impl StateRepr {
+    fn new(state: &State) -> Self {
+	let mut actions = vec![vec![]; state.get_round() as usize + 1];
+	for (round, _, action) in state.action_history() {
+	    actions[round as usize].push(action.to_acpc_token());
+	}
+	let hole_cards = (0..state.game.number_of_players())
+	    .map(|p| {
+		let cards = state.hole_cards(p).unwrap();
+		if cards.contains(&NOT_DEALT) {
+		    vec![]
+		} else {
+		    cards.iter().map(|&c| card_to_string(c)).collect()
+		}
+	    })
+	    .collect();
+	StateRepr {
+	    version: VERSION,
+	    game: GameRepr::new(&state.game),
+	    hand_id: state.hand_id(),
+	    round: state.get_round(),
+	    finished: state.is_finished(),
+	    actions,
+	    board_cards: state.board_cards().iter().map(|&c| card_to_string(c)).collect(),
+	    hole_cards,
+	}
+    }
+
+    fn into_state(self) -> Result<State, String> {
+	if self.version != VERSION {
+	    return Err(format!("Unsupported version {}", self.version));
+	}
+	let game = self.game.into_game()?;
+	let mut state = State::new_with_hand_id(game, self.hand_id);
+	for (round, tokens) in self.actions.iter().enumerate() {
+	    for token in tokens {
+		if state.get_round() as usize != round {
+		    return Err(format!("Action {:?} is not in round {}", token, round));
+		}
+		let action = Action::from_acpc_token(token).map_err(|e| e.to_string())?;
+		state.do_action(action).map_err(|e| e.to_string())?;
+	    }
+	}
+	if state.get_round() != self.round || state.is_finished() != self.finished {
+	    return Err("round and finished do not match the actions".to_owned());
+	}
+
+	let num_hole_cards = state.game.num_hole_cards() as usize;
+	if self.hole_cards.len() != state.game.number_of_players() as usize {
+	    return Err("hole_cards need a value for each player".to_owned());
+	}
+	let mut hole_cards = vec![];
+	for (p, cards) in self.hole_cards.iter().enumerate() {
+	    if !cards.is_empty() && cards.len() != num_hole_cards {
+		return Err(format!("Player {} needs {} hole cards", p, num_hole_cards));
+	    }
+	    hole_cards.push(parse_card_strings(cards)?);
+	}
+	let board_cards = parse_card_strings(&self.board_cards)?;
+	if !state.game.rounds().any(|r| state.game.sum_board_cards(r) as usize == board_cards.len()) {
+	    return Err(format!("{} board cards are not the board of any round", board_cards.len()));
+	}
+	// all the cards together must be distinct cards of the deck
+	let mut used = vec![];
+	for &card in hole_cards.iter().flatten().chain(&board_cards) {
+	    if !state.game.in_deck(card) {
+		return Err(format!("Card {} is not in the deck", card_to_string(card)));
+	    }
+	    if used.contains(&card) {
+		return Err(format!("Card {} is used more than once", card_to_string(card)));
+	    }
+	    used.push(card);
+	}
+	for (p, cards) in hole_cards.iter().enumerate() {
+	    state.state_.holeCards[p][..cards.len()].copy_from_slice(cards);
+	}
+	state.state_.boardCards[..board_cards.len()].copy_from_slice(&board_cards);
+	Ok(state)
+    }
+}
+
+fn parse_card_strings(cards: &[String]) -> Result<Vec<Card>, String> {
+    cards.iter().map(|c| parse_card(c).map_err(|e| e.to_string())).collect()
+}
+
+impl Serialize for Game {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+	GameRepr::new(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Game {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+	GameRepr::deserialize(deserializer)?.into_game().map_err(D::Error::custom)
+    }
+}
+
+impl Serialize for State {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+	StateRepr::new(self).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for State {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+	StateRepr::deserialize(deserializer)?.into_state().map_err(D::Error::custom)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_cards;
+    use std::fs::File;
+
+    fn get_state() -> State {
+	let file = File::open("resources/holdem.nolimit.3p.game").unwrap();
+	State::new(Game::read(file))
+    }
+
+    #[test]
+    fn game_round_trip() {
+	for name in ["leduc.limit.2p", "holdem.nolimit.3p", "holdem.nolimit.2p.reverse_blinds"] {
+	    let file = File::open(format!("resources/{}.game", name)).unwrap();
+	    let game = Game::read(file);
+	    let json = serde_json::to_string(&game).unwrap();
+	    let restored: Game = serde_json::from_str(&json).unwrap();
+	    assert_eq!(game, restored);
+	    assert_eq!(game.stack_size(0), restored.stack_size(0));
+	    assert_eq!(game.blind_size(1), restored.blind_size(1));
+	    assert_eq!(game.number_of_rounds(), restored.number_of_rounds());
+	}
+    }
+
+    #[test]
+    fn game_nolimit_raise_size() {
+	let file = File::open("resources/holdem.nolimit.3p.game").unwrap();
+	let json = serde_json::to_string(&Game::read(file)).unwrap();
+	assert!(json.contains("\"raise_size\":[0,0,0,0]"), "{}", json);
+    }
+
+    #[test]
+    fn game_invalid() {
+	let file = File::open("resources/holdem.nolimit.3p.game").unwrap();
+	let json = serde_json::to_string(&Game::read(file)).unwrap();
+	let invalid = json.replace("\"nolimit\"", "\"potlimit\"");
+	assert!(serde_json::from_str::<Game>(&invalid).is_err());
+	let invalid = json.replace("\"num_players\":3", "\"num_players\":2");
+	assert!(serde_json::from_str::<Game>(&invalid).is_err());
+    }
+
+    #[test]
+    fn state_round_trip() {
+	let mut state = get_state();
+	for action in [Action::Raise(200), Action::Call, Action::Call, Action::Raise(1000)] {
+	    state.do_action(action).unwrap();
+	}
+	state.set_hole_cards(1, &parse_cards("AsKh").unwrap()).unwrap();
+	state.set_board_cards(&parse_cards("7h8d9c").unwrap()).unwrap();
+
+	let json = serde_json::to_string(&state).unwrap();
+	assert!(json.contains("\"version\":1"));
+	assert!(json.contains("\"actions\":[[\"r200\",\"c\",\"c\"],[\"r1000\"]]"));
+	let restored: State = serde_json::from_str(&json).unwrap();
+	assert_eq!(state.to_string(), restored.to_string());
+	assert_eq!(state.action_history().collect::<Vec<_>>(),
+		   restored.action_history().collect::<Vec<_>>());
+	assert_eq!(state.spents(), restored.spents());
+	assert_eq!(state.current_player(), restored.current_player());
+	assert_eq!(state.hole_cards(0), restored.hole_cards(0));
+	assert_eq!(state.hole_cards(1), restored.hole_cards(1));
+	assert_eq!(state.board_cards(), restored.board_cards());
+    }
+
+    #[test]
+    fn state_invalid() {
+	let state = get_state();
+	let json = serde_json::to_string(&state).unwrap();
+	let invalid = json.replace("\"actions\":[[]]", "\"actions\":[[\"r100\"]]");
+	assert!(serde_json::from_str::<State>(&invalid).is_err());
+	let invalid = json.replace("\"version\":1", "\"version\":2");
+	assert!(serde_json::from_str::<State>(&invalid).is_err());
+    }
+
+    #[test]
+    fn state_invalid_cards() {
+	let file = File::open("resources/leduc.limit.2p.game").unwrap();
+	let mut state = State::new(Game::read(file));
+	state.set_hole_cards(0, &parse_cards("Qh").unwrap()).unwrap();
+	state.set_hole_cards(1, &parse_cards("Ks").unwrap()).unwrap();
+	state.set_board_cards(&parse_cards("As").unwrap()).unwrap();
+	let json = serde_json::to_string(&state).unwrap();
+	assert!(serde_json::from_str::<State>(&json).is_ok());
+	// the same card twice, in two hands or in a hand and on the board
+	let invalid = json.replace("[\"Ks\"]", "[\"Qh\"]");
+	assert!(serde_json::from_str::<State>(&invalid).is_err());
+	let invalid = json.replace("[\"As\"]", "[\"Ks\"]");
+	assert!(serde_json::from_str::<State>(&invalid).is_err());
+	// Leduc only has the queens, kings and aces of two suits
+	let invalid = json.replace("[\"Ks\"]", "[\"Kd\"]");
+	assert!(serde_json::from_str::<State>(&invalid).is_err());
+	// a board which is not the board of any round
+	let invalid = json.replace("[\"As\"]", "[\"As\",\"Ah\"]");
+	assert!(serde_json::from_str::<State>(&invalid).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{Action, State};
+
+
+/// A node of a `GameTree`.
+#[derive(Debug, Clone)]
+pub struct Node {
+    parent: Option<usize>,
+    action: Option<Action>,
+    children: Vec<usize>,
+    state: State,
+}
+
+impl Node {
+    /// Index of the parent node, `None` for the root.
+    pub fn parent(&self) -> Option<usize> {
+	self.parent
+    }
+
+    /// The action which leads from the parent to this node.
+    pub fn action(&self) -> Option<Action> {
+	self.action
+    }
+
+    /// Indices of the child nodes in the order of `State::legal_actions`.
+    pub fn children(&self) -> &[usize] {
+	&self.children
+    }
+
+    pub fn state(&self) -> &State {
+	&self.state
+    }
+
+    pub fn is_terminal(&self) -> bool {
+	self.state.is_finished()
+    }
+}
+
+
+/// The betting tree below a state with the nodes stored in an arena.
+///
+/// Nodes are referred to by their index, the root being 0, and the children
+/// follow `State::legal_actions`. Cards are not dealt, so the tree only
+/// has player nodes and terminal nodes and is meant for small games.
+#[derive(Debug, Clone)]
+pub struct GameTree {
+    nodes: Vec<Node>,
+}
+
+impl GameTree {
+    pub fn new(root: State) -> Self {
+	let mut tree = GameTree { nodes: vec![] };
+	tree.push(None, None, root);
+	tree
+    }
+
+    fn push(&mut self, parent: Option<usize>, action: Option<Action>, state: State) -> usize {
+	let idx = self.nodes.len();
+	let actions = state.legal_actions();
+	self.nodes.push(Node { parent, action, children: vec![], state });
+	for action in actions {
+	    let next = self.nodes[idx].state.apply(action).unwrap();
+	    let child = self.push(Some(idx), Some(action), next);
+	    self.nodes[idx].children.push(child);
+	}
+	idx
+    }
+
+    pub fn root(&self) -> &Node {
+	&self.nodes[0]
+    }
+
+    pub fn node(&self, idx: usize) -> Option<&Node> {
+	self.nodes.get(idx)
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+	&self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+	self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+	self.nodes.is_empty()
+    }
+
+    /// Indices of the terminal nodes in depth-first order.
+    pub fn terminals(&self) -> impl Iterator<Item = usize> + '_ {
+	(0..self.nodes.len()).filter(move |&i| self.nodes[i].is_terminal())
+    }
+
+    /// The actions from the root to the node `idx`.
+    pub fn history(&self, idx: usize) -> Vec<Action> {
+	let mut history = vec![];
+	let mut node = &self.nodes[idx];
+	while let (Some(action), Some(parent)) = (node.action, node.parent) {
+	    history.push(action);
+	    node = &self.nodes[parent];
+	}
+	history.reverse();
+	history
+    }
+
+    /// The action histories of all the terminal nodes.
+    pub fn terminal_histories(&self) -> Vec<Vec<Action>> {
+	self.terminals().map(|i| self.history(i)).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Game;
+    use std::fs::File;
+
+    fn get_tree() -> GameTree {
+	let file = File::open("resources/leduc.limit.2p.game").unwrap();
+	GameTree::new(State::new(Game::read(file)))
+    }
+
+    #[test]
+    fn terminals() {
+	let tree = get_tree();
+	// 4 ways to end the first round by folding, and 5 to reach the
+	// second round followed by 4 folds or 5 showdowns.
+	let histories = tree.terminal_histories();
+	assert_eq!(4 + 5 * 9, histories.len());
+	assert_eq!(vec![Action::Call, Action::Call, Action::Call, Action::Call], histories[0]);
+	assert!(histories.contains(&vec![Action::Raise(2), Action::Fold]));
+	for i in tree.terminals() {
+	    let mut state = tree.root().state().clone();
+	    for action in tree.history(i) {
+		state.do_action(action).unwrap();
+	    }
+	    assert!(state.is_finished());
+	    assert_eq!(tree.node(i).unwrap().state().spents(), state.spents());
+	}
+    }
+
+    #[test]
+    fn for_each_child() {
+	fn count_terminals(state: &mut State) -> usize {
+	    if state.is_finished() {
+		return 1;
+	    }
+	    let mut count = 0;
+	    state.for_each_child(|child, _| count += count_terminals(child));
+	    count
+	}
+	let tree = get_tree();
+	let mut state = tree.root().state().clone();
+	assert_eq!(tree.terminals().count(), count_terminals(&mut state));
+	assert_eq!(0, state.num_actions());
+
+	let mut children = vec![];
+	state.for_each_child(|child, action| children.push((action, child.current_player())));
+	let expected = tree.root().children().iter()
+	    .map(|&i| tree.node(i).unwrap())
+	    .map(|n| (n.action().unwrap(), n.state().current_player()))
+	    .collect::<Vec<_>>();
+	assert_eq!(expected, children);
+    }
+
+    #[test]
+    fn nodes() {
+	let tree = get_tree();
+	let root = tree.root();
+	assert_eq!(None, root.parent());
+	assert_eq!(None, root.action());
+	assert_eq!(2, root.children().len());
+	let child = tree.node(root.children()[1]).unwrap();
+	assert_eq!(Some(0), child.parent());
+	assert_eq!(Some(Action::Raise(2)), child.action());
+	assert_eq!(1, child.state().current_player());
+	assert!(!child.is_terminal());
+	assert_eq!(tree.len(), 1 + tree.nodes().iter().map(|n| n.children().len()).sum::<usize>());
+    }
+}